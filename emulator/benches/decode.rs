@@ -0,0 +1,36 @@
+//! Rough instructions per second, to compare opcode decoding with and without the
+//! `opcode-array` feature:
+//!
+//! cargo bench --bench decode
+//! cargo bench --bench decode --features opcode-array
+
+use std::time::Instant;
+
+use emulator::Cpu;
+
+const INSTRUCTIONS: usize = 20_000_000;
+
+fn main() {
+    let mut cpu = Cpu::default();
+    // loop: INX; DEY; LDA $10,X; BNE loop; JMP loop
+    cpu.load(vec![0xE8, 0x88, 0xB5, 0x10, 0xD0, 0xF9, 0x4C, 0x00, 0x06]);
+    cpu.reset();
+
+    let start = Instant::now();
+    for _ in 0..INSTRUCTIONS {
+        cpu.run_single_cycle();
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{} instructions in {:.2?} ({:.1}M instructions/s, opcode-array {})",
+        INSTRUCTIONS,
+        elapsed,
+        INSTRUCTIONS as f64 / elapsed.as_secs_f64() / 1_000_000.0,
+        if cfg!(feature = "opcode-array") {
+            "on"
+        } else {
+            "off"
+        },
+    );
+}
@@ -0,0 +1,42 @@
+use super::{Cpu, RunResult};
+
+/// How a conformance program ended, see [`Cpu::wait_for_result`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TestOutcome {
+    Pass,
+    Fail,
+    /// Neither sentinel was reached within the cycle budget.
+    Timeout,
+    /// The program stopped (`BRK` or an error) before reaching a sentinel.
+    Halted(RunResult),
+}
+
+impl Cpu {
+    /// Run until the program counter reaches `success_pc` or `fail_pc`, which test programs
+    /// usually mark with a jump to itself, or until `max_cycles` have elapsed.
+    pub fn wait_for_result(
+        &mut self,
+        success_pc: u16,
+        fail_pc: u16,
+        max_cycles: usize,
+    ) -> TestOutcome {
+        let target = self.cycles.saturating_add(max_cycles);
+
+        loop {
+            if self.program_counter == success_pc {
+                return TestOutcome::Pass;
+            }
+            if self.program_counter == fail_pc {
+                return TestOutcome::Fail;
+            }
+            if self.cycles >= target {
+                return TestOutcome::Timeout;
+            }
+
+            match self.run_single_cycle() {
+                RunResult::Running => {}
+                result => return TestOutcome::Halted(result),
+            }
+        }
+    }
+}
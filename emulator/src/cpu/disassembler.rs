@@ -0,0 +1,173 @@
+use super::{
+    addressing_mode::AddressingMode,
+    memory::Memory,
+    opcode::{self, OpCode},
+    Cpu,
+};
+
+/// Operand of a decoded instruction, by addressing mode.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Operand {
+    Implied,
+    Accumulator,
+    Immediate(u8),
+    ZeroPage(u8),
+    ZeroPageX(u8),
+    ZeroPageY(u8),
+    Absolute(u16),
+    AbsoluteX(u16),
+    AbsoluteY(u16),
+    /// `JMP ($nnnn)`
+    Indirect(u16),
+    IndirectX(u8),
+    IndirectY(u8),
+    /// Branch displacement, relative to the following instruction.
+    Relative(i8),
+}
+
+/// One instruction as found in memory, see [`Cpu::decode_at`].
+#[derive(Debug, Clone)]
+pub struct DecodedInstruction {
+    pub address: u16,
+    pub opcode: &'static OpCode,
+    pub operand: Operand,
+    pub bytes: Vec<u8>,
+}
+
+impl DecodedInstruction {
+    /// Where a branch lands when taken.
+    pub fn branch_target(&self) -> Option<u16> {
+        match self.operand {
+            Operand::Relative(offset) => Some(
+                self.address
+                    .wrapping_add(self.bytes.len() as u16)
+                    .wrapping_add(offset as u16),
+            ),
+            _ => None,
+        }
+    }
+}
+
+impl Cpu {
+    /// Decode the instruction at `addr`, `None` if the byte there isn't a known opcode.
+    pub fn decode_at(&self, addr: u16) -> Option<DecodedInstruction> {
+        let opcode = opcode::decode(self.peek(addr))?;
+        let bytes = self.mem_read_range(addr, opcode.len as usize);
+        let byte = bytes.get(1).copied().unwrap_or_default();
+        let word = u16::from_le_bytes([byte, bytes.get(2).copied().unwrap_or_default()]);
+
+        let operand = match opcode.mode {
+            AddressingMode::Immediate => Operand::Immediate(byte),
+            AddressingMode::ZeroPage => Operand::ZeroPage(byte),
+            AddressingMode::ZeroPageX => Operand::ZeroPageX(byte),
+            AddressingMode::ZeroPageY => Operand::ZeroPageY(byte),
+            AddressingMode::Absolute => Operand::Absolute(word),
+            AddressingMode::AbsoluteX => Operand::AbsoluteX(word),
+            AddressingMode::AbsoluteY => Operand::AbsoluteY(word),
+            AddressingMode::IndirectX => Operand::IndirectX(byte),
+            AddressingMode::IndirectY => Operand::IndirectY(byte),
+            AddressingMode::NoneAddressing => match (opcode.code, opcode.len) {
+                // Accumulator shifts
+                (0x0a | 0x2a | 0x4a | 0x6a, _) => Operand::Accumulator,
+                (0x6c, _) => Operand::Indirect(word),
+                (_, 3) => Operand::Absolute(word),
+                (_, 2) => Operand::Relative(byte as i8),
+                _ => Operand::Implied,
+            },
+        };
+
+        Some(DecodedInstruction {
+            address: addr,
+            opcode,
+            operand,
+            bytes,
+        })
+    }
+
+    /// Render the instruction at `addr` as assembly, e.g. `LDA #$02`. Bytes that don't decode to
+    /// a known opcode are rendered as `.byte $nn`.
+    pub fn disassemble(&self, addr: u16) -> String {
+        let instruction = match self.decode_at(addr) {
+            Some(instruction) => instruction,
+            None => return format!(".byte ${:02X}", self.peek(addr)),
+        };
+
+        let operand = match instruction.operand {
+            Operand::Implied => return instruction.opcode.repr.to_string(),
+            Operand::Accumulator => "A".to_string(),
+            Operand::Immediate(byte) => format!("#${:02X}", byte),
+            Operand::ZeroPage(byte) => format!("${:02X}", byte),
+            Operand::ZeroPageX(byte) => format!("${:02X},X", byte),
+            Operand::ZeroPageY(byte) => format!("${:02X},Y", byte),
+            Operand::Absolute(word) => format!("${:04X}", word),
+            Operand::AbsoluteX(word) => format!("${:04X},X", word),
+            Operand::AbsoluteY(word) => format!("${:04X},Y", word),
+            Operand::Indirect(word) => format!("(${:04X})", word),
+            Operand::IndirectX(byte) => format!("(${:02X},X)", byte),
+            Operand::IndirectY(byte) => format!("(${:02X}),Y", byte),
+            // Branches, displayed with their resolved target
+            Operand::Relative(_) => format!("${:04X}", instruction.branch_target().unwrap()),
+        };
+
+        format!("{} {}", instruction.opcode.repr, operand)
+    }
+
+    /// Byte length of the instruction at `addr`, `1` for bytes that aren't a known opcode.
+    pub fn instruction_len_at(&self, addr: u16) -> u8 {
+        opcode::decode(self.peek(addr))
+            .map(|opcode| opcode.len)
+            .unwrap_or(1)
+    }
+
+    /// Address-annotated assembly listing of the instructions in `start..end`, one per line:
+    /// address, raw bytes, mnemonic and operand.
+    pub fn listing(&self, start: u16, end: u16) -> String {
+        self.listing_until(start, end as u32)
+    }
+
+    /// `listing` with an end that can go up to `0x10000`, to list the last bytes of memory.
+    pub(crate) fn listing_until(&self, start: u16, end: u32) -> String {
+        let mut listing = String::new();
+        let mut addr = start as u32;
+
+        while addr < end {
+            let len = self.instruction_len_at(addr as u16);
+            let bytes = (0..len as u16)
+                .map(|offset| format!("{:02X}", self.peek((addr as u16).wrapping_add(offset))))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            let line = format!(
+                "{:04X}  {:<8}  {}",
+                addr,
+                bytes,
+                self.disassemble(addr as u16)
+            );
+            listing.push_str(line.trim_end());
+            listing.push('\n');
+
+            addr += len as u32;
+        }
+
+        listing
+    }
+
+    /// Walk instructions from `start` using their lengths and return the address of the first
+    /// `BRK`, or `None` when the end of the address space is reached first.
+    pub fn find_brk(&self, start: u16) -> Option<u16> {
+        let mut addr = start as u32;
+
+        while addr <= u16::MAX as u32 {
+            let code = self.peek(addr as u16);
+            if code == 0x00 {
+                return Some(addr as u16);
+            }
+
+            addr += opcode::decode(code)
+                .map(|opcode| opcode.len as u32)
+                .unwrap_or(1);
+        }
+
+        None
+    }
+}
@@ -0,0 +1,26 @@
+use std::fmt::Display;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CpuError {
+    /// The byte at `address` doesn't decode to any known instruction.
+    IllegalOpcode { opcode: u8, address: u16 },
+    /// Strict mode only, the address was read before anything was written to it.
+    UninitializedRead(u16),
+}
+
+impl Display for CpuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CpuError::IllegalOpcode { opcode, address } => write!(
+                f,
+                "Illegal opcode 0x{:02X} at address 0x{:04X}",
+                opcode, address
+            ),
+            CpuError::UninitializedRead(address) => {
+                write!(f, "Read from uninitialized address 0x{:04X}", address)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CpuError {}
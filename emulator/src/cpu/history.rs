@@ -0,0 +1,113 @@
+use std::collections::VecDeque;
+
+use super::{state::CpuState, Cpu};
+
+/// What an instruction changed, enough to revert it.
+#[derive(Debug, Clone)]
+struct Step {
+    state: CpuState,
+    call_depth: usize,
+    last_branch_taken: Option<bool>,
+    last_instruction_cycles: usize,
+    // (address, previous value), in write order
+    writes: Vec<(u16, u8)>,
+}
+
+/// Bounded record of the last executed instructions, see [`Cpu::set_history_depth`].
+#[derive(Debug, Default, Clone)]
+pub(crate) struct History {
+    depth: usize,
+    steps: VecDeque<Step>,
+    recording: bool,
+}
+
+impl History {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.depth > 0
+    }
+
+    fn begin(&mut self, step: Step) {
+        self.steps.push_back(step);
+        self.recording = true;
+    }
+
+    /// Stop recording the current step, dropping it unless `keep`.
+    pub(crate) fn end(&mut self, keep: bool) {
+        if !self.recording {
+            return;
+        }
+        self.recording = false;
+
+        if !keep {
+            self.steps.pop_back();
+        } else if self.steps.len() > self.depth {
+            self.steps.pop_front();
+        }
+    }
+
+    pub(crate) fn record_write(&mut self, addr: u16, previous: u8) {
+        if !self.recording {
+            return;
+        }
+        if let Some(step) = self.steps.back_mut() {
+            step.writes.push((addr, previous));
+        }
+    }
+}
+
+impl Cpu {
+    /// Start recording the instruction about to run, when history is enabled.
+    pub(crate) fn begin_history_step(&mut self) {
+        if !self.history.is_enabled() {
+            return;
+        }
+
+        let step = Step {
+            state: self.state(),
+            call_depth: self.call_depth,
+            last_branch_taken: self.last_branch_taken,
+            last_instruction_cycles: self.last_instruction_cycles,
+            writes: Vec::new(),
+        };
+        self.history.begin(step);
+    }
+
+    /// Keep enough information about the last `depth` instructions to undo them with
+    /// `step_back`. `0`, the default, disables recording.
+    pub fn set_history_depth(&mut self, depth: usize) {
+        self.history.depth = depth;
+        while self.history.steps.len() > depth {
+            self.history.steps.pop_front();
+        }
+    }
+
+    /// Revert the last recorded instruction, registers, memory and the per-instruction bookkeeping
+    /// (`last_instruction_cycles`, `last_branch_taken`, call depth) included. Illegal opcodes
+    /// don't execute anything and aren't recorded. Returns `false` when there is nothing left to
+    /// undo.
+    pub fn step_back(&mut self) -> bool {
+        let Some(step) = self.history.steps.pop_back() else {
+            return false;
+        };
+
+        for &(addr, previous) in step.writes.iter().rev() {
+            self.memory[addr as usize] = previous;
+        }
+
+        let state = step.state;
+        self.register_a = state.register_a;
+        self.register_x = state.register_x;
+        self.register_y = state.register_y;
+        self.status = state.status;
+        self.program_counter = state.program_counter;
+        self.stack_pointer = state.stack_pointer;
+        self.cycles = state.cycles;
+        self.call_depth = step.call_depth;
+        self.last_branch_taken = step.last_branch_taken;
+        self.last_instruction_cycles = step.last_instruction_cycles;
+        self.instructions = self.instructions.saturating_sub(1);
+        self.halted = false;
+
+        true
+    }
+}
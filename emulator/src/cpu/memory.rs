@@ -1,6 +1,11 @@
+#[cfg(feature = "tracing")]
 use tracing::instrument;
 
-use super::{addressing_mode::AddressingMode, Cpu};
+use std::{collections::HashMap, ops::RangeInclusive};
+
+use super::{addressing_mode::AddressingMode, stack::STACK_RESET, Cpu};
+
+const RAM_SIZE: usize = 0x0800;
 
 pub trait Memory {
     fn mem_read(&self, addr: u16) -> u8;
@@ -8,78 +13,254 @@ pub trait Memory {
     fn mem_write(&mut self, addr: u16, data: u8);
 
     fn mem_read_u16(&self, addr: u16) -> u16 {
-        u16::from_le_bytes([self.mem_read(addr), self.mem_read(addr + 1)])
+        u16::from_le_bytes([self.mem_read(addr), self.mem_read(addr.wrapping_add(1))])
     }
 
     fn mem_write_u16(&mut self, addr: u16, data: u16) {
         let data = data.to_le_bytes();
 
         self.mem_write(addr, data[0]);
-        self.mem_write(addr + 1, data[1]);
+        self.mem_write(addr.wrapping_add(1), data[1]);
+    }
+
+    /// Read `addr` for inspection, without the side effects a read can have on mapped IO.
+    /// Defaults to `mem_read` for memories without any.
+    fn peek(&self, addr: u16) -> u8 {
+        self.mem_read(addr)
+    }
+
+    fn peek_u16(&self, addr: u16) -> u16 {
+        u16::from_le_bytes([self.peek(addr), self.peek(addr.wrapping_add(1))])
+    }
+
+    /// Peek `len` contiguous bytes, wrapping around at the end of the address space.
+    fn mem_read_range(&self, start: u16, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|offset| self.peek(start.wrapping_add(offset as u16)))
+            .collect()
     }
-    fn get_operand_address(&self, mode: AddressingMode) -> u16;
 }
 
-impl Memory for Cpu {
-    #[instrument]
+/// Memory-mapped peripheral, accesses to its mapped range are routed here instead of RAM.
+pub trait IoHandler {
+    fn read(&mut self, addr: u16) -> u8;
+
+    fn write(&mut self, addr: u16, data: u8);
+}
+
+/// What RAM holds at power on, see [`Cpu::power_on`].
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum RamPattern {
+    #[default]
+    AllZero,
+    AllOnes,
+    /// `0x00` at even addresses, `0xFF` at odd ones.
+    Alternating,
+}
+
+impl RamPattern {
+    fn byte_at(self, addr: usize) -> u8 {
+        match self {
+            RamPattern::AllZero => 0x00,
+            RamPattern::AllOnes => 0xFF,
+            RamPattern::Alternating if addr & 1 == 0 => 0x00,
+            RamPattern::Alternating => 0xFF,
+        }
+    }
+}
+
+pub(crate) type IoMapping = (RangeInclusive<u16>, Box<dyn IoHandler>);
+
+/// Sparse memory backed by a map, unset addresses read as `0`. Handy for unit tests that don't
+/// want to drag a whole 64KB buffer around.
+#[derive(Debug, Default)]
+pub struct SparseMemory {
+    cells: HashMap<u16, u8>,
+}
+
+impl Memory for SparseMemory {
     fn mem_read(&self, addr: u16) -> u8 {
-        log::trace!("Reading memory address 0x{:X?}", addr);
-        self.memory[addr as usize]
+        self.cells.get(&addr).copied().unwrap_or_default()
     }
 
-    #[instrument]
     fn mem_write(&mut self, addr: u16, data: u8) {
-        self.memory[addr as usize] = data;
-        log::trace!("Writing 0x{:X?} at 0x{:X?}", data, addr);
+        self.cells.insert(addr, data);
     }
+}
 
-    fn get_operand_address(&self, mode: AddressingMode) -> u16 {
-        match mode {
-            AddressingMode::Immediate => self.program_counter,
-            AddressingMode::ZeroPage => self.mem_read(self.program_counter) as u16,
-            AddressingMode::Absolute => self.mem_read_u16(self.program_counter),
+/// Resolve the address targeted by the operand at `program_counter` for the given mode, `None`
+/// for `AddressingMode::NoneAddressing`.
+pub fn operand_address<M: Memory + ?Sized>(
+    memory: &M,
+    mode: AddressingMode,
+    program_counter: u16,
+    register_x: u8,
+    register_y: u8,
+) -> Option<u16> {
+    let addr = match mode {
+        AddressingMode::Immediate => program_counter,
+        AddressingMode::ZeroPage => memory.mem_read(program_counter) as u16,
+        AddressingMode::Absolute => memory.mem_read_u16(program_counter),
 
-            AddressingMode::ZeroPageX => {
-                let pos = self.mem_read(self.program_counter);
+        AddressingMode::ZeroPageX => {
+            let pos = memory.mem_read(program_counter);
 
-                pos.wrapping_add(self.register_x) as u16
-            }
-            AddressingMode::ZeroPageY => {
-                let pos = self.mem_read(self.program_counter);
+            pos.wrapping_add(register_x) as u16
+        }
+        AddressingMode::ZeroPageY => {
+            let pos = memory.mem_read(program_counter);
 
-                pos.wrapping_add(self.register_y) as u16
-            }
+            pos.wrapping_add(register_y) as u16
+        }
 
-            AddressingMode::AbsoluteX => {
-                let base = self.mem_read_u16(self.program_counter);
+        AddressingMode::AbsoluteX => {
+            let base = memory.mem_read_u16(program_counter);
 
-                base.wrapping_add(self.register_x as u16)
-            }
-            AddressingMode::AbsoluteY => {
-                let base = self.mem_read_u16(self.program_counter);
+            base.wrapping_add(register_x as u16)
+        }
+        AddressingMode::AbsoluteY => {
+            let base = memory.mem_read_u16(program_counter);
 
-                base.wrapping_add(self.register_y as u16)
-            }
+            base.wrapping_add(register_y as u16)
+        }
 
-            AddressingMode::IndirectX => {
-                let base = self.mem_read(self.program_counter);
+        AddressingMode::IndirectX => {
+            let base = memory.mem_read(program_counter);
 
-                let ptr: u8 = (base as u8).wrapping_add(self.register_x);
+            let ptr: u8 = base.wrapping_add(register_x);
 
-                let lo = self.mem_read(ptr as u16);
-                let hi = self.mem_read(ptr.wrapping_add(1) as u16);
-                (hi as u16) << 8 | (lo as u16)
-            }
-            AddressingMode::IndirectY => {
-                let base = self.mem_read(self.program_counter);
-
-                let lo = self.mem_read(base as u16);
-                let hi = self.mem_read((base as u8).wrapping_add(1) as u16);
-                let deref_base = (hi as u16) << 8 | (lo as u16);
-                let deref = deref_base.wrapping_add(self.register_y as u16);
-                deref
+            let lo = memory.mem_read(ptr as u16);
+            let hi = memory.mem_read(ptr.wrapping_add(1) as u16);
+            (hi as u16) << 8 | (lo as u16)
+        }
+        AddressingMode::IndirectY => {
+            let base = memory.mem_read(program_counter);
+
+            let lo = memory.mem_read(base as u16);
+            let hi = memory.mem_read(base.wrapping_add(1) as u16);
+            let deref_base = (hi as u16) << 8 | (lo as u16);
+
+            deref_base.wrapping_add(register_y as u16)
+        }
+        AddressingMode::NoneAddressing => return None,
+    };
+
+    Some(addr)
+}
+
+impl Memory for Cpu {
+    #[cfg_attr(feature = "tracing", instrument)]
+    fn mem_read(&self, addr: u16) -> u8 {
+        if self.trace_memory {
+            log::trace!("Reading memory address 0x{:X?}", addr);
+        }
+        if let Some((_, handler)) = self
+            .io
+            .borrow_mut()
+            .iter_mut()
+            .find(|(range, _)| range.contains(&addr))
+        {
+            return handler.read(addr);
+        }
+        if self.strict && !self.is_initialized(addr) && self.uninitialized_read.get().is_none() {
+            self.uninitialized_read.set(Some(addr));
+        }
+        self.memory[addr as usize]
+    }
+
+    /// Straight from RAM: skips mapped IO, strict mode tracking and memory tracing.
+    fn peek(&self, addr: u16) -> u8 {
+        self.memory[addr as usize]
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
+    fn mem_write(&mut self, addr: u16, data: u8) {
+        match self
+            .io
+            .get_mut()
+            .iter_mut()
+            .find(|(range, _)| range.contains(&addr))
+        {
+            Some((_, handler)) => handler.write(addr, data),
+            None => {
+                if self.history.is_enabled() {
+                    self.history.record_write(addr, self.memory[addr as usize]);
+                }
+                self.memory[addr as usize] = data;
             }
-            AddressingMode::NoneAddressing => todo!(),
         }
+        self.mark_initialized(addr, 1);
+        if self.trace_memory {
+            log::trace!("Writing 0x{:X?} at 0x{:X?}", data, addr);
+        }
+    }
+}
+
+impl Cpu {
+    /// Report reads from addresses that were never written (or loaded) as
+    /// [`CpuError::UninitializedRead`](super::error::CpuError::UninitializedRead). Off by default.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Pattern `power_on` fills RAM with, `RamPattern::AllZero` by default.
+    pub fn set_power_on_ram_pattern(&mut self, pattern: RamPattern) {
+        self.ram_pattern = pattern;
+    }
+
+    /// Simulate powering the console on: the 2KB of internal RAM (`0x0000-0x07FF`) gets filled
+    /// with the power on pattern, then the CPU resets. Filled cells still count as uninitialized
+    /// in strict mode.
+    pub fn power_on(&mut self) {
+        for (addr, byte) in self.memory[..RAM_SIZE].iter_mut().enumerate() {
+            *byte = self.ram_pattern.byte_at(addr);
+        }
+        self.initialized[..RAM_SIZE / 64].fill(0);
+        self.stack_pointer = STACK_RESET;
+
+        self.reset();
+    }
+
+    /// Copy of `0x0000-0x00FF`, read straight from RAM without going through mapped IO.
+    pub fn zero_page(&self) -> [u8; 256] {
+        let mut zero_page = [0; 256];
+        zero_page.copy_from_slice(&self.memory[..256]);
+
+        zero_page
+    }
+
+    pub(crate) fn mark_initialized(&mut self, start: u16, len: usize) {
+        for offset in 0..len {
+            let addr = start.wrapping_add(offset as u16) as usize;
+            self.initialized[addr / 64] |= 1 << (addr % 64);
+        }
+    }
+
+    fn is_initialized(&self, addr: u16) -> bool {
+        self.initialized[addr as usize / 64] & 1 << (addr % 64) != 0
+    }
+
+    /// Route reads and writes in `range` to `handler`. Later mappings don't override earlier
+    /// ones on overlapping addresses.
+    pub fn map_io(&mut self, range: RangeInclusive<u16>, handler: Box<dyn IoHandler>) {
+        self.io.get_mut().push((range, handler));
+    }
+
+    /// Address targeted by the current instruction's operand. This used to be a method of the
+    /// `Memory` trait; it only makes sense with registers, so other memories should use
+    /// [`operand_address`] instead.
+    ///
+    /// # Panics
+    ///
+    /// On `AddressingMode::NoneAddressing`, which has no operand address.
+    pub fn get_operand_address(&self, mode: AddressingMode) -> u16 {
+        operand_address(
+            self,
+            mode,
+            self.program_counter,
+            self.register_x,
+            self.register_y,
+        )
+        .expect("Implied and relative instructions have no operand address")
     }
 }
@@ -1,23 +1,41 @@
 #![allow(unused)] // please leave me alone clippy
 
 pub mod addressing_mode;
+pub mod conformance;
+pub mod disassembler;
+pub mod error;
 pub mod flags;
+mod history;
 pub mod memory;
 pub mod opcode;
+mod rng;
 pub mod stack;
+pub mod state;
+pub mod summary;
+pub mod trace;
 
 use core::num;
-use std::fmt::Debug;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt::Debug,
+    io::Write,
+    ops::RangeInclusive,
+};
 
 use enumflags2::BitFlags;
 
 use addressing_mode::AddressingMode;
+use error::CpuError;
 use flags::CpuFlags;
+use history::History;
 use log::info;
-use memory::Memory;
-use opcode::OPCODES_MAP;
+use memory::{IoMapping, Memory, RamPattern};
+use opcode::OpCode;
+use rng::Rng;
 use stack::Stack;
-use tracing::{field, instrument};
+#[cfg(feature = "tracing")]
+use tracing::instrument;
 
 pub struct Cpu {
     // accumulator
@@ -27,9 +45,40 @@ pub struct Cpu {
     pub status: BitFlags<CpuFlags>,
     pub program_counter: u16,
     pub stack_pointer: u8,
-    memory: [u8; u16::MAX as usize],
+    pub cycles: usize,
+    last_instruction_cycles: usize,
+    last_branch_taken: Option<bool>,
+    call_depth: usize,
+    call_depth_warning: Option<usize>,
+    instructions: usize,
+    last_result: RunResult,
+    stack_page: u16,
+    program_len: u16,
+    run_mode: RunMode,
+    program_guard: ProgramGuard,
+    rng_seed: u64,
+    rng: Rng,
+    input_address: u16,
+    halted: bool,
+    // Boxed so moving or cloning a Cpu doesn't copy 64KB through the stack
+    memory: Box<[u8; MEMORY_SIZE]>,
+    ram_pattern: RamPattern,
+    // One bit per address, set once it has been written or loaded
+    initialized: Vec<u64>,
+    strict: bool,
+    lenient: bool,
+    uninitialized_read: Cell<Option<u16>>,
+    trace_memory: bool,
+    dump_on_load: bool,
+    trace_sink: Option<Box<dyn Write>>,
+    history: History,
+    handlers: HashMap<u8, OpcodeHandler>,
+    io: RefCell<Vec<IoMapping>>,
 }
 
+/// Custom opcode implementation, consulted before the built-in dispatch.
+pub type OpcodeHandler = Box<dyn FnMut(&mut Cpu, AddressingMode)>;
+
 impl Default for Cpu {
     fn default() -> Self {
         Self {
@@ -39,17 +88,80 @@ impl Default for Cpu {
             status: BitFlags::default(),
             program_counter: 0,
             stack_pointer: 0,
-            memory: [0; u16::MAX as usize],
+            cycles: 0,
+            last_instruction_cycles: 0,
+            last_branch_taken: None,
+            call_depth: 0,
+            call_depth_warning: None,
+            instructions: 0,
+            last_result: RunResult::Running,
+            stack_page: stack::STACK,
+            program_len: 0,
+            run_mode: RunMode::default(),
+            program_guard: ProgramGuard::default(),
+            rng_seed: 0,
+            rng: Rng::new(0),
+            input_address: crate::LAST_PRESSED_BUTTON_ADDRESS.into(),
+            halted: false,
+            memory: vec![0; MEMORY_SIZE].into_boxed_slice().try_into().unwrap(),
+            ram_pattern: RamPattern::default(),
+            initialized: vec![0; MEMORY_SIZE / 64],
+            strict: false,
+            lenient: false,
+            uninitialized_read: Cell::new(None),
+            trace_memory: false,
+            dump_on_load: false,
+            trace_sink: None,
+            history: History::default(),
+            handlers: HashMap::new(),
+            io: RefCell::new(Vec::new()),
         }
     }
 }
 
+// The whole 0x0000-0xFFFF address space
+const MEMORY_SIZE: usize = u16::MAX as usize + 1;
 const RESET_ADDRESS: u16 = 0xFFFC;
+const IRQ_ADDRESS: u16 = 0xFFFE;
+const NMI_ADDRESS: u16 = 0xFFFA;
 const GAME_START_ADDRESS: u16 = 0x0600;
+// The reset sequence takes as long as an interrupt before the first instruction runs
+const RESET_CYCLES: usize = 7;
+// Charged for opcodes missing from the table, run by a handler or skipped in lenient mode, like
+// the official NOP
+const UNKNOWN_OPCODE_CYCLES: usize = 2;
 
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum RunResult {
     Running,
     Done,
+    /// Execution stopped on an error. After `CpuError::IllegalOpcode` the program counter is
+    /// left on the faulty byte; after `CpuError::UninitializedRead` the instruction has fully
+    /// executed and the program counter is past it.
+    Error(CpuError),
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum RunMode {
+    /// `BRK` halts the CPU, which is what the test programs and the snake demo rely on.
+    #[default]
+    StopOnBrk,
+    /// `BRK` goes through the IRQ vector like on hardware, execution never stops on its own and
+    /// has to be bounded by the caller.
+    Continuous,
+}
+
+/// What to do when the program counter leaves the range of the loaded program, see
+/// [`Cpu::program_range`].
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum ProgramGuard {
+    /// Keep going, programs are free to jump anywhere.
+    #[default]
+    Off,
+    /// Keep going but log a warning for each instruction fetched outside the program.
+    Warn,
+    /// Stop with `RunResult::Done` before executing outside the program.
+    Stop,
 }
 
 impl Debug for Cpu {
@@ -61,23 +173,147 @@ impl Debug for Cpu {
             .field("status", &self.status)
             .field("program_counter", &self.program_counter)
             .field("stack_pointer", &self.stack_pointer)
+            .field("cycles", &self.cycles)
+            .field("halted", &self.halted)
             .field("memory", &"[...]")
             .finish()
     }
 }
 
+/// Deep copy of the registers, memory and configuration. Custom opcode handlers, mapped IO and
+/// the trace sink can't be cloned, so the copy starts without any.
+impl Clone for Cpu {
+    fn clone(&self) -> Self {
+        Self {
+            register_a: self.register_a,
+            register_x: self.register_x,
+            register_y: self.register_y,
+            status: self.status,
+            program_counter: self.program_counter,
+            stack_pointer: self.stack_pointer,
+            cycles: self.cycles,
+            last_instruction_cycles: self.last_instruction_cycles,
+            last_branch_taken: self.last_branch_taken,
+            call_depth: self.call_depth,
+            call_depth_warning: self.call_depth_warning,
+            instructions: self.instructions,
+            last_result: self.last_result,
+            stack_page: self.stack_page,
+            program_len: self.program_len,
+            run_mode: self.run_mode,
+            program_guard: self.program_guard,
+            rng_seed: self.rng_seed,
+            rng: self.rng,
+            input_address: self.input_address,
+            halted: self.halted,
+            memory: self.memory.clone(),
+            ram_pattern: self.ram_pattern,
+            initialized: self.initialized.clone(),
+            strict: self.strict,
+            lenient: self.lenient,
+            uninitialized_read: self.uninitialized_read.clone(),
+            trace_memory: self.trace_memory,
+            dump_on_load: self.dump_on_load,
+            trace_sink: None,
+            history: self.history.clone(),
+            handlers: HashMap::new(),
+            io: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+/// Two CPUs are equal when their registers and memory match, configuration (handlers, run mode,
+/// ...) is not compared.
+impl PartialEq for Cpu {
+    fn eq(&self, other: &Self) -> bool {
+        self.state() == other.state() && self.memory == other.memory
+    }
+}
+
 impl Cpu {
+    /// Build a CPU whose memory is preloaded with the given `(address, value)` pairs.
+    pub fn with_memory(image: &[(u16, u8)]) -> Self {
+        let mut cpu = Self::default();
+        for &(addr, data) in image {
+            cpu.mem_write(addr, data);
+        }
+
+        cpu
+    }
+
     pub fn run_single_cycle(&mut self) -> RunResult {
         self.run_single_cycle_with_callback(|_| {})
     }
 
     pub fn run(&mut self) {
-        loop {
-            match self.run_single_cycle_with_callback(|_| {}) {
+        while let RunResult::Running = self.run_single_cycle_with_callback(|_| {}) {}
+    }
+
+    /// Execute whole instructions until at least `budget` cycles have elapsed or the CPU stops,
+    /// returning how many cycles actually ran.
+    pub fn run_cycles(&mut self, budget: usize) -> usize {
+        self.run_cycles_with(budget, Cpu::run_single_cycle)
+    }
+
+    /// Like `run_cycles`, stepping with `step_with_rng` so the RNG byte is fresh before every
+    /// instruction.
+    pub fn run_cycles_with_rng(&mut self, budget: usize) -> usize {
+        self.run_cycles_with(budget, Cpu::step_with_rng)
+    }
+
+    fn run_cycles_with<F>(&mut self, budget: usize, mut step: F) -> usize
+    where
+        F: FnMut(&mut Cpu) -> RunResult,
+    {
+        let start = self.cycles;
+        while self.cycles - start < budget {
+            if step(self) != RunResult::Running {
+                break;
+            }
+        }
+
+        self.cycles - start
+    }
+
+    /// Cycles taken by the last executed instruction, page crossing and branch penalties
+    /// included.
+    pub fn last_instruction_cycles(&self) -> usize {
+        self.last_instruction_cycles
+    }
+
+    /// Whether the most recent conditional branch jumped, `None` until one runs after reset.
+    pub fn last_branch_taken(&self) -> Option<bool> {
+        self.last_branch_taken
+    }
+
+    /// Log a warning whenever `JSR` nesting goes deeper than `threshold`, which usually means
+    /// runaway recursion. Execution itself is unaffected. `None`, the default, disables it.
+    pub fn set_call_depth_warning(&mut self, threshold: Option<usize>) {
+        self.call_depth_warning = threshold;
+    }
+
+    /// Current `JSR` nesting, as counted since the last reset.
+    pub fn call_depth(&self) -> usize {
+        self.call_depth
+    }
+
+    /// Run code already in memory starting at `pc`, skipping the reset vector.
+    pub fn run_from(&mut self, pc: u16) {
+        self.program_counter = pc;
+        self.halted = false;
+        self.run();
+    }
+
+    /// Execute whole instructions until the cycle counter reaches or exceeds `target`.
+    pub fn run_until_cycle(&mut self, target: usize) -> RunResult {
+        while self.cycles < target {
+            match self.run_single_cycle() {
                 RunResult::Running => {}
-                RunResult::Done => break,
+                result => return result,
             }
         }
+
+        RunResult::Running
     }
 
     pub fn run_single_cycle_with_callback<F>(&mut self, mut callback: F) -> RunResult
@@ -85,23 +321,139 @@ impl Cpu {
         F: FnMut(&mut Cpu),
     {
         callback(self);
+        if self.stopped_by_program_guard() {
+            self.halted = true;
+            self.last_result = RunResult::Done;
+
+            return RunResult::Done;
+        }
+
+        if self.trace_sink.is_some() {
+            self.write_trace();
+        }
+        let cycles = self.cycles;
+        self.uninitialized_read.set(None);
+        self.begin_history_step();
+        let mut result = self.execute_next_instruction();
+        if let Some(address) = self.uninitialized_read.take() {
+            log::error!("Read from uninitialized address 0x{:04X}", address);
+            self.halted = true;
+            result = RunResult::Error(CpuError::UninitializedRead(address));
+        }
+        // Everything but an illegal opcode ran the instruction to completion
+        let executed = !matches!(result, RunResult::Error(CpuError::IllegalOpcode { .. }));
+        self.history.end(executed);
+        self.last_instruction_cycles = self.cycles - cycles;
+        if executed {
+            self.instructions += 1;
+        }
+        self.last_result = result;
+
+        result
+    }
+
+    fn stopped_by_program_guard(&self) -> bool {
+        if self.program_guard == ProgramGuard::Off {
+            return false;
+        }
+
+        let outside_program =
+            self.program_len > 0 && !self.program_range().contains(&self.program_counter);
+
+        match self.program_guard {
+            ProgramGuard::Warn if outside_program => {
+                log::warn!(
+                    "Executing outside of the loaded program at 0x{:04X}",
+                    self.program_counter
+                );
+                false
+            }
+            ProgramGuard::Stop => outside_program,
+            _ => false,
+        }
+    }
+
+    fn execute_next_instruction(&mut self) -> RunResult {
         log::debug!("{:?}", &self);
         log::debug!("Reading next opcode.");
         let opcode = self.mem_read(self.program_counter);
-        self.program_counter += 1;
+        self.program_counter = self.program_counter.wrapping_add(1);
         let program_counter_state = self.program_counter;
 
-        let opcode = (*&OPCODES_MAP).get(&opcode).copied().expect(&format!(
-            "Illegal opcode instruction provided 0x{:X?}",
-            opcode
-        ));
+        // Skip hashing the opcode when no handler is registered, which is the common case
+        let handler = if self.handlers.is_empty() {
+            None
+        } else {
+            self.handlers.remove(&opcode)
+        };
+        if let Some(mut handler) = handler {
+            log::debug!("Executing custom handler for opcode 0x{:X?}", opcode);
+            let code = opcode;
+            let opcode = opcode::decode(code);
+            let mode = opcode
+                .map(|opcode| opcode.mode)
+                .unwrap_or(AddressingMode::NoneAddressing);
+
+            handler(self, mode);
+            self.handlers.insert(code, handler);
+
+            // Unknown opcodes have no length, their handler is in charge of moving the program
+            // counter past any operand.
+            match opcode {
+                Some(opcode) => {
+                    self.cycles += opcode.cycles as usize;
+                    if program_counter_state == self.program_counter {
+                        self.program_counter =
+                            self.program_counter.wrapping_add(opcode.len as u16 - 1);
+                    }
+                }
+                None => self.cycles += UNKNOWN_OPCODE_CYCLES,
+            }
+
+            return RunResult::Running;
+        }
+
+        let opcode = match opcode::decode(opcode) {
+            Some(opcode) => opcode,
+            None if self.lenient => {
+                log::warn!(
+                    "Skipping unknown opcode 0x{:02X} at 0x{:04X} as a NOP",
+                    opcode,
+                    self.program_counter.wrapping_sub(1)
+                );
+                self.program_counter = self
+                    .program_counter
+                    .wrapping_add(opcode::encoded_len(opcode) as u16 - 1);
+                self.cycles += UNKNOWN_OPCODE_CYCLES;
+
+                return RunResult::Running;
+            }
+            None => {
+                self.program_counter = self.program_counter.wrapping_sub(1);
+                self.halted = true;
+                let error = CpuError::IllegalOpcode {
+                    opcode,
+                    address: self.program_counter,
+                };
+                log::error!("{}", error);
+
+                return RunResult::Error(error);
+            }
+        };
 
         log::debug!("Executing instruction {:?}", &opcode);
+        self.cycles += opcode.cycles as usize + self.page_cross_penalty(opcode);
 
         match opcode.repr {
             "ADC" => self.adc(opcode.mode),
+            "ALR" => self.alr(opcode.mode),
+            "ANC" => self.anc(opcode.mode),
             "AND" => self.and(opcode.mode),
-            "ASL" => self.asl(opcode.mode),
+            "ARR" => self.arr(opcode.mode),
+            "ASL" => match opcode.code {
+                0x0A => self.asl_accumulator(),
+                _ => self.asl(opcode.mode),
+            },
             "BCC" => self.branch(!self.status.contains(CpuFlags::CarryBit)),
             "BCS" => self.branch(self.status.contains(CpuFlags::CarryBit)),
             "BEQ" => self.branch(self.status.contains(CpuFlags::Zero)),
@@ -109,7 +461,13 @@ impl Cpu {
             "BMI" => self.branch(self.status.contains(CpuFlags::Negative)),
             "BNE" => self.branch(!self.status.contains(CpuFlags::Zero)),
             "BPL" => self.branch(!self.status.contains(CpuFlags::Negative)),
-            "BRK" => return RunResult::Done,
+            "BRK" => match self.run_mode {
+                RunMode::StopOnBrk => {
+                    self.halted = true;
+                    return RunResult::Done;
+                }
+                RunMode::Continuous => self.brk(),
+            },
             "BVC" => self.branch(!self.status.contains(CpuFlags::Overflow)),
             "BVS" => self.branch(self.status.contains(CpuFlags::Overflow)),
             "CLC" => self.status.remove(CpuFlags::CarryBit),
@@ -119,11 +477,11 @@ impl Cpu {
             "CMP" => self.compare(opcode.mode, self.register_a),
             "CPX" => self.compare(opcode.mode, self.register_x),
             "CPY" => self.compare(opcode.mode, self.register_y),
-            "DEC" => self.dec(),
+            "DEC" => self.dec(opcode.mode),
             "DEX" => self.dex(),
             "DEY" => self.dey(),
             "EOR" => self.eor(opcode.mode),
-            "INC" => self.inc(),
+            "INC" => self.inc(opcode.mode),
             "INX" => self.inx(),
             "INY" => self.iny(),
             "JMP" => match opcode.code {
@@ -153,9 +511,10 @@ impl Cpu {
                 }
             },
             "JSR" => {
-                self.stack_push_u16(self.program_counter + 2 - 1);
+                self.stack_push_u16(self.program_counter.wrapping_add(1));
                 let target = self.mem_read_u16(self.program_counter);
                 self.program_counter = target;
+                self.enter_subroutine();
             }
             "LDA" => self.lda(opcode.mode),
             "LDX" => self.ldx(opcode.mode),
@@ -179,7 +538,10 @@ impl Cpu {
                 _ => self.ror(opcode.mode),
             },
             "RTI" => self.rti(),
-            "RTS" => self.program_counter = self.stack_pop_u16() + 1,
+            "RTS" => {
+                self.program_counter = self.stack_pop_u16().wrapping_add(1);
+                self.call_depth = self.call_depth.saturating_sub(1);
+            }
             "SBC" => self.sbc(opcode.mode),
             "SEC" => self.status.insert(CpuFlags::CarryBit),
             "SED" => self.status.insert(CpuFlags::DecimalMode),
@@ -193,6 +555,7 @@ impl Cpu {
             "TXA" => self.txa(),
             "TXS" => self.txs(),
             "TYA" => self.tya(),
+            "AXS" => self.axs(opcode.mode),
 
             _ => unreachable!(
                 "Invalid byte {:X?} - Dumping memory: {:?}",
@@ -201,7 +564,7 @@ impl Cpu {
         }
 
         if program_counter_state == self.program_counter {
-            self.program_counter += opcode.len as u16 - 1;
+            self.program_counter = self.program_counter.wrapping_add(opcode.len as u16 - 1);
         }
 
         RunResult::Running
@@ -212,6 +575,13 @@ impl Cpu {
         self.register_a = 0;
         self.register_x = 0;
         self.status = BitFlags::default();
+        self.cycles = RESET_CYCLES;
+        self.instructions = 0;
+        self.last_branch_taken = None;
+        self.call_depth = 0;
+        self.last_result = RunResult::Running;
+        self.halted = false;
+        self.rng = Rng::new(self.rng_seed);
 
         self.program_counter = self.mem_read_u16(RESET_ADDRESS);
         info!("Reset done.");
@@ -226,28 +596,217 @@ impl Cpu {
     pub fn load(&mut self, program: Vec<u8>) {
         self.memory[(GAME_START_ADDRESS as usize)..(GAME_START_ADDRESS as usize + program.len())]
             .copy_from_slice(&program[..]);
+        self.mark_initialized(GAME_START_ADDRESS, program.len());
         self.mem_write_u16(RESET_ADDRESS, GAME_START_ADDRESS);
+        self.program_len = program.len() as u16;
+        self.halted = false;
+
+        if self.dump_on_load {
+            let range = self.program_range();
+            let listing = self.listing_until(*range.start(), *range.end() as u32 + 1);
+            log::debug!("Loaded program:\n{}", listing);
+        }
+    }
+
+    /// Copy a memory image verbatim starting at `0x0000`, reset vector included, so that `reset`
+    /// jumps wherever the image says rather than to the default game start address.
+    pub fn load_image(&mut self, image: &[u8]) {
+        assert!(
+            image.len() <= MEMORY_SIZE,
+            "Memory image is larger than the address space ({} bytes)",
+            image.len()
+        );
+
+        self.memory[..image.len()].copy_from_slice(image);
+        self.mark_initialized(0, image.len());
+        // Images don't have a program range, see `program_range`
+        self.program_len = 0;
+        self.halted = false;
+    }
+
+    /// Addresses of the program passed to the last `load`, empty after `load_image`. The end is
+    /// inclusive so that a program can run up to `0xFFFF`.
+    pub fn program_range(&self) -> RangeInclusive<u16> {
+        let end = GAME_START_ADDRESS
+            .wrapping_add(self.program_len)
+            .wrapping_sub(1);
+
+        GAME_START_ADDRESS..=end
+    }
+
+    /// Whether execution stopped for any reason: `BRK`, an illegal opcode, a strict mode error or
+    /// the program guard. `run_summary().result` tells which. Cleared by `reset`, `load` and
+    /// `load_image`.
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Log every memory read and write at the trace level. Off by default since it floods the
+    /// output and slows down the wasm build considerably.
+    pub fn set_trace_memory(&mut self, enabled: bool) {
+        self.trace_memory = enabled;
+    }
+
+    /// Log the disassembly of every program passed to `load`, at the debug level.
+    pub fn set_dump_on_load(&mut self, enabled: bool) {
+        self.dump_on_load = enabled;
+    }
+
+    pub fn set_run_mode(&mut self, run_mode: RunMode) {
+        self.run_mode = run_mode;
+    }
+
+    pub fn run_mode(&self) -> RunMode {
+        self.run_mode
+    }
+
+    pub fn set_program_guard(&mut self, program_guard: ProgramGuard) {
+        self.program_guard = program_guard;
+    }
+
+    /// Seed the generator behind `refresh_rng`. The seed is kept across `reset`, which restarts
+    /// the random sequence from the beginning.
+    pub fn set_rng_seed(&mut self, seed: u64) {
+        self.rng_seed = seed;
+        self.rng = Rng::new(seed);
+    }
+
+    /// Write the next random value (between 1 and 15, like the snake demo expects) to
+    /// `RNG_ADDRESS`.
+    pub fn refresh_rng(&mut self) {
+        let value = 1 + (self.rng.next_u64() % 15) as u8;
+        self.mem_write(crate::RNG_ADDRESS.into(), value);
     }
 
-    #[instrument]
+    /// `refresh_rng` then execute a single instruction, the way the snake demo expects to be
+    /// stepped.
+    pub fn step_with_rng(&mut self) -> RunResult {
+        self.refresh_rng();
+        self.run_single_cycle()
+    }
+
+    /// Skip opcodes missing from the table as NOPs (with a warning) instead of stopping with
+    /// `CpuError::IllegalOpcode`. Off by default.
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.lenient = lenient;
+    }
+
+    /// Address programs poll for the last pressed key, `LAST_PRESSED_BUTTON_ADDRESS` by default.
+    pub fn set_input_address(&mut self, addr: u16) {
+        self.input_address = addr;
+    }
+
+    pub fn input_address(&self) -> u16 {
+        self.input_address
+    }
+
+    /// Write `key` where the program expects input, see `set_input_address`.
+    pub fn set_input(&mut self, key: u8) {
+        self.mem_write(self.input_address, key);
+    }
+
+    /// Move the stack to another page. The NES (and any stock 6502) uses `0x0100`, this is only
+    /// meant for experimenting with variants.
+    ///
+    /// # Panics
+    ///
+    /// If `page` isn't page aligned (a multiple of `0x100`).
+    pub fn set_stack_page(&mut self, page: u16) {
+        assert!(
+            page & 0xFF == 0,
+            "Stack page 0x{:04X} is not page aligned",
+            page
+        );
+        self.stack_page = page;
+    }
+
+    /// Override (or add) the implementation of `code`. The handler receives the addressing mode
+    /// from the opcode table, or `NoneAddressing` for codes the table doesn't know about.
+    pub fn register_handler(&mut self, code: u8, handler: OpcodeHandler) {
+        self.handlers.insert(code, handler);
+    }
+
+    /// The 0, 1 or 2 operand bytes following the opcode at the current program counter.
+    pub fn operand_bytes(&self, opcode: &OpCode) -> Vec<u8> {
+        self.mem_read_range(
+            self.program_counter.wrapping_add(1),
+            opcode.len as usize - 1,
+        )
+    }
+
+    /// Address of the instruction following the one at the current program counter, assuming
+    /// no branch or jump is taken. Useful to place temporary breakpoints when stepping over.
+    pub fn next_pc(&self) -> u16 {
+        let len = self.instruction_len_at(self.program_counter);
+
+        self.program_counter.wrapping_add(len as u16)
+    }
+
+    /// Addressing mode of the instruction about to execute, `None` if the byte at the program
+    /// counter isn't a known opcode.
+    pub fn current_addressing_mode(&self) -> Option<AddressingMode> {
+        opcode::decode(self.peek(self.program_counter)).map(|opcode| opcode.mode)
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
     fn adc(&mut self, mode: AddressingMode) {
         let addr = self.get_operand_address(mode);
 
         self.add_to_register_a(self.mem_read(addr));
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
+    fn alr(&mut self, mode: AddressingMode) {
+        let addr = self.get_operand_address(mode);
+        let data = self.mem_read(addr) & self.register_a;
+
+        self.status.set(CpuFlags::CarryBit, data & 1 == 1);
+        self.set_register_a(data >> 1);
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
+    fn anc(&mut self, mode: AddressingMode) {
+        self.and(mode);
+        self.status
+            .set(CpuFlags::CarryBit, self.status.contains(CpuFlags::Negative));
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
+    fn arr(&mut self, mode: AddressingMode) {
+        let addr = self.get_operand_address(mode);
+        let mut data = (self.mem_read(addr) & self.register_a) >> 1;
+        if self.status.contains(CpuFlags::CarryBit) {
+            data |= CpuFlags::Negative.into_bitflags().bits();
+        }
+        self.set_register_a(data);
+
+        // Carry and overflow come from bits 6 and 5 of the result rather than from the rotation
+        let bit_6 = data >> 6 & 1;
+        let bit_5 = data >> 5 & 1;
+        self.status.set(CpuFlags::CarryBit, bit_6 == 1);
+        self.status.set(CpuFlags::Overflow, bit_6 ^ bit_5 == 1);
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
+    fn asl_accumulator(&mut self) {
+        let mut data = self.register_a;
+        self.status.set(CpuFlags::CarryBit, data >> 7 == 1);
+        data <<= 1;
+        self.set_register_a(data);
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
     fn asl(&mut self, mode: AddressingMode) {
         let addr = self.get_operand_address(mode);
         let mut data = self.mem_read(addr);
         self.status.set(CpuFlags::CarryBit, data >> 7 == 1);
-        data = data << 1;
+        data <<= 1;
 
         self.mem_write(addr, data);
         self.update_zero_and_negative_flags(data);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn and(&mut self, mode: AddressingMode) {
         let addr = self.get_operand_address(mode);
         let data = self.mem_read(addr);
@@ -255,22 +814,76 @@ impl Cpu {
         self.set_register_a(data & self.register_a);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
+    fn axs(&mut self, mode: AddressingMode) {
+        let addr = self.get_operand_address(mode);
+        let data = self.mem_read(addr);
+        let and = self.register_a & self.register_x;
+
+        self.status.set(CpuFlags::CarryBit, data <= and);
+        self.register_x = and.wrapping_sub(data);
+        self.update_zero_and_negative_flags(self.register_x);
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
     fn branch(&mut self, condition: bool) {
+        self.last_branch_taken = Some(condition);
         if !condition {
             return;
         }
 
         let jump: i8 = self.mem_read(self.program_counter) as i8;
-        let jump_addr = self
-            .program_counter
-            .wrapping_add(1)
-            .wrapping_add(jump as u16);
+        let next_instruction = self.program_counter.wrapping_add(1);
+        let jump_addr = next_instruction.wrapping_add(jump as u16);
 
+        // +1 for a taken branch, +1 more when it lands on another page
+        self.cycles += 1 + (next_instruction & 0xFF00 != jump_addr & 0xFF00) as usize;
         self.program_counter = jump_addr;
     }
 
-    #[instrument]
+    /// Extra cycle taken by indexed reads whose effective address lands on another page than
+    /// the base address. Stores and read-modify-write instructions always pay it in their base
+    /// count.
+    fn page_cross_penalty(&self, opcode: &OpCode) -> usize {
+        if matches!(
+            opcode.repr,
+            "STA" | "ASL" | "LSR" | "ROL" | "ROR" | "INC" | "DEC"
+        ) {
+            return 0;
+        }
+
+        let (base, index) = match opcode.mode {
+            AddressingMode::AbsoluteX => (self.peek_u16(self.program_counter), self.register_x),
+            AddressingMode::AbsoluteY => (self.peek_u16(self.program_counter), self.register_y),
+            AddressingMode::IndirectY => {
+                let ptr = self.peek(self.program_counter);
+                let lo = self.peek(ptr as u16);
+                let hi = self.peek(ptr.wrapping_add(1) as u16);
+
+                (u16::from_le_bytes([lo, hi]), self.register_y)
+            }
+            _ => return 0,
+        };
+
+        (base & 0xFF00 != base.wrapping_add(index as u16) & 0xFF00) as usize
+    }
+
+    fn enter_subroutine(&mut self) {
+        self.call_depth += 1;
+
+        if let Some(threshold) = self.call_depth_warning {
+            if self.call_depth > threshold {
+                log::warn!(
+                    "JSR nesting depth {} exceeds {} calling 0x{:04X}",
+                    self.call_depth,
+                    threshold,
+                    self.program_counter
+                );
+            }
+        }
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
     fn bit(&mut self, mode: AddressingMode) {
         let addr = self.get_operand_address(mode);
         let data = self.mem_read(addr);
@@ -287,7 +900,46 @@ impl Cpu {
         );
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
+    fn brk(&mut self) {
+        // The byte following BRK is padding, the return address skips it
+        self.interrupt(self.program_counter.wrapping_add(1), IRQ_ADDRESS, true);
+    }
+
+    /// Non-maskable interrupt, jumps through the vector at 0xFFFA.
+    pub fn interrupt_nmi(&mut self) {
+        self.interrupt(self.program_counter, NMI_ADDRESS, false);
+        self.cycles += 7;
+    }
+
+    /// Maskable interrupt request, ignored while `DisableInterrupts` is set.
+    pub fn interrupt_irq(&mut self) {
+        if self.status.contains(CpuFlags::DisableInterrupts) {
+            return;
+        }
+
+        self.interrupt(self.program_counter, IRQ_ADDRESS, false);
+        self.cycles += 7;
+    }
+
+    fn interrupt(&mut self, return_address: u16, vector: u16, break_flag: bool) {
+        self.stack_push_u16(return_address);
+        self.stack_push(self.pushed_status(break_flag));
+
+        self.status.insert(CpuFlags::DisableInterrupts);
+        self.program_counter = self.mem_read_u16(vector);
+    }
+
+    // Bit 5 is always set on the stack, bit 4 (Break) only when pushed by PHP/BRK
+    fn pushed_status(&self, break_flag: bool) -> u8 {
+        let mut status = self.status;
+        status.insert(CpuFlags::_Unused);
+        status.set(CpuFlags::Break, break_flag);
+
+        status.bits()
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
     fn compare(&mut self, mode: AddressingMode, compare_with: u8) {
         let addr = self.get_operand_address(mode);
         let data = self.mem_read(addr);
@@ -297,50 +949,56 @@ impl Cpu {
         self.update_zero_and_negative_flags(compare_with.wrapping_sub(data));
     }
 
-    #[instrument]
-    fn dec(&mut self) {
-        self.register_a = self.register_a.wrapping_sub(1);
-        self.update_zero_and_negative_flags(self.register_a);
+    #[cfg_attr(feature = "tracing", instrument)]
+    fn dec(&mut self, mode: AddressingMode) {
+        let addr = self.get_operand_address(mode);
+        let data = self.mem_read(addr).wrapping_sub(1);
+
+        self.mem_write(addr, data);
+        self.update_zero_and_negative_flags(data);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn dex(&mut self) {
         self.register_x = self.register_x.wrapping_sub(1);
         self.update_zero_and_negative_flags(self.register_x);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn dey(&mut self) {
         self.register_y = self.register_y.wrapping_sub(1);
         self.update_zero_and_negative_flags(self.register_y);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn eor(&mut self, mode: AddressingMode) {
         let addr = self.get_operand_address(mode);
         let data = self.mem_read(addr);
         self.set_register_a(data ^ self.register_a);
     }
 
-    #[instrument]
-    fn inc(&mut self) {
-        self.register_a = self.register_a.wrapping_add(1);
-        self.update_zero_and_negative_flags(self.register_a);
+    #[cfg_attr(feature = "tracing", instrument)]
+    fn inc(&mut self, mode: AddressingMode) {
+        let addr = self.get_operand_address(mode);
+        let data = self.mem_read(addr).wrapping_add(1);
+
+        self.mem_write(addr, data);
+        self.update_zero_and_negative_flags(data);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn inx(&mut self) {
         self.register_x = self.register_x.wrapping_add(1);
         self.update_zero_and_negative_flags(self.register_x);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn iny(&mut self) {
         self.register_y = self.register_y.wrapping_add(1);
         self.update_zero_and_negative_flags(self.register_y);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn lda(&mut self, mode: AddressingMode) {
         let addr = self.get_operand_address(mode);
         let value = self.mem_read(addr);
@@ -348,132 +1006,127 @@ impl Cpu {
         self.set_register_a(value);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn ldx(&mut self, mode: AddressingMode) {
         let addr = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
         self.register_x = self.mem_read(addr);
 
         self.update_zero_and_negative_flags(self.register_x);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn ldy(&mut self, mode: AddressingMode) {
         let addr = self.get_operand_address(mode);
-        self.register_x = self.mem_read(addr);
+        self.register_y = self.mem_read(addr);
 
         self.update_zero_and_negative_flags(self.register_y);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn lsr_accumulator(&mut self) {
         let mut data = self.register_a;
         self.status.set(CpuFlags::CarryBit, data & 1 == 1);
-        data = data >> 1;
+        data >>= 1;
         self.set_register_a(data);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn lsr(&mut self, mode: AddressingMode) {
         let addr = self.get_operand_address(mode);
         let mut data = self.mem_read(addr);
 
         self.status.set(CpuFlags::CarryBit, data & 1 == 1);
-        data = data >> 1;
+        data >>= 1;
         self.mem_write(addr, data);
         self.update_zero_and_negative_flags(data);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn ora(&mut self, mode: AddressingMode) {
         let addr = self.get_operand_address(mode);
         let data = self.mem_read(addr);
         self.set_register_a(data | self.register_a);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn php(&mut self) {
-        let mut status = self.status.clone();
-        status.insert(CpuFlags::Break);
-        status.insert(CpuFlags::_Unused);
-
-        self.stack_push(status.bits());
+        self.stack_push(self.pushed_status(true));
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn pla(&mut self) {
         let value = self.stack_pop();
         self.set_register_a(value);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn plp(&mut self) {
         self.pop_status_from_stack();
         self.status.remove(CpuFlags::Break);
         self.status.insert(CpuFlags::_Unused);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn rol_accumulator(&mut self) {
         let mut data = self.register_a;
         let old_carry = self.status.contains(CpuFlags::CarryBit);
 
         self.status.set(CpuFlags::CarryBit, data >> 7 == 1);
-        data = data << 1;
+        data <<= 1;
         if old_carry {
-            data = data | 1;
+            data |= 1;
         }
 
         self.set_register_a(data);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn rol(&mut self, mode: AddressingMode) {
         let addr = self.get_operand_address(mode);
         let mut data = self.mem_read(addr);
         let old_carry = self.status.contains(CpuFlags::CarryBit);
 
         self.status.set(CpuFlags::CarryBit, data >> 7 == 1);
-        data = data << 1;
+        data <<= 1;
         if old_carry {
-            data = data | 1;
+            data |= 1;
         }
 
         self.mem_write(addr, data);
         self.update_zero_and_negative_flags(data);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn ror_accumulator(&mut self) {
         let mut data = self.register_a;
         let old_carry = self.status.contains(CpuFlags::CarryBit);
 
         self.status.set(CpuFlags::CarryBit, data & 1 == 1);
-        data = data >> 1;
+        data >>= 1;
         if old_carry {
-            data = data | CpuFlags::Negative.into_bitflags().bits();
+            data |= CpuFlags::Negative.into_bitflags().bits();
         }
 
         self.set_register_a(data);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn ror(&mut self, mode: AddressingMode) {
         let addr = self.get_operand_address(mode);
         let mut data = self.mem_read(addr);
         let old_carry = self.status.contains(CpuFlags::CarryBit);
 
         self.status.set(CpuFlags::CarryBit, data & 1 == 1);
-        data = data >> 1;
+        data >>= 1;
         if old_carry {
-            data = data | CpuFlags::Negative.into_bitflags().bits();
+            data |= CpuFlags::Negative.into_bitflags().bits();
         }
 
         self.mem_write(addr, data);
         self.update_zero_and_negative_flags(data);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn rti(&mut self) {
         self.pop_status_from_stack();
         self.status.remove(CpuFlags::Break);
@@ -482,7 +1135,7 @@ impl Cpu {
         self.program_counter = self.stack_pop_u16();
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn sbc(&mut self, mode: AddressingMode) {
         let addr = self.get_operand_address(mode);
         let data = self.mem_read(addr);
@@ -490,60 +1143,60 @@ impl Cpu {
         self.add_to_register_a((data as i8).wrapping_neg().wrapping_sub(1) as u8)
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn sta(&mut self, mode: AddressingMode) {
         let addr = self.get_operand_address(mode);
         self.mem_write(addr, self.register_a);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn stx(&mut self, mode: AddressingMode) {
         let addr = self.get_operand_address(mode);
         self.mem_write(addr, self.register_x);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn sty(&mut self, mode: AddressingMode) {
         let addr = self.get_operand_address(mode);
         self.mem_write(addr, self.register_y);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn tax(&mut self) {
         self.register_x = self.register_a;
         self.update_zero_and_negative_flags(self.register_x);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn tay(&mut self) {
         self.register_y = self.register_a;
         self.update_zero_and_negative_flags(self.register_y);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn tsx(&mut self) {
         self.register_x = self.stack_pointer;
         self.update_zero_and_negative_flags(self.register_x);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn txa(&mut self) {
         self.register_a = self.register_x;
         self.update_zero_and_negative_flags(self.register_a);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn txs(&mut self) {
         self.stack_pointer = self.register_x;
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn tya(&mut self) {
         self.register_a = self.register_y;
         self.update_zero_and_negative_flags(self.register_a);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn update_zero_and_negative_flags(&mut self, result: u8) {
         self.status.set(CpuFlags::Zero, result == 0);
 
@@ -559,13 +1212,8 @@ impl Cpu {
     }
 
     fn add_to_register_a(&mut self, value: u8) {
-        let sum = self.register_a as u16
-            + value as u16
-            + self
-                .status
-                .contains(CpuFlags::CarryBit)
-                .then_some(1u16)
-                .unwrap_or_default();
+        let sum =
+            self.register_a as u16 + value as u16 + self.status.contains(CpuFlags::CarryBit) as u16;
 
         self.status.set(CpuFlags::CarryBit, sum > u8::MAX as u16);
 
@@ -588,82 +1236,1655 @@ impl Cpu {
 #[cfg(test)]
 mod test {
     use super::*;
+    use opcode::OPCODES_MAP;
+
+    /// Generate a test loading `program`, optionally overriding fields after reset, running it
+    /// and asserting fields and flags:
+    ///
+    /// `test_opcode!(name, [0xAA, 0x00], { register_a: 10 } => { register_x: 10 }, clear: [Zero]);`
+    macro_rules! test_opcode {
+        (
+            $name:ident, [$($byte:expr),* $(,)?],
+            $({ $($init:ident: $init_value:expr),* $(,)? })? => { $($field:ident: $value:expr),* $(,)? }
+            $(, set: [$($set:ident),* $(,)?])?
+            $(, clear: [$($clear:ident),* $(,)?])? $(,)?
+        ) => {
+            #[test]
+            fn $name() {
+                let mut cpu = Cpu::default();
+                cpu.load(vec![$($byte),*]);
+                cpu.reset();
+                $($(cpu.$init = $init_value;)*)?
+                cpu.run();
+
+                $(assert_eq!(cpu.$field, $value, stringify!($field));)*
+                $($(assert!(cpu.status.contains(CpuFlags::$set), stringify!($set));)*)?
+                $($(assert!(!cpu.status.contains(CpuFlags::$clear), stringify!($clear));)*)?
+            }
+        };
+    }
+
+    test_opcode!(
+        test_0xa9_lda_immediate_load_data,
+        [0xA9, 0x05, 0x00],
+        => { register_a: 0x05 },
+        clear: [Zero, Negative],
+    );
+
+    test_opcode!(
+        test_0xa9_lda_zero_flag,
+        [0xA9, 0x00, 0x00],
+        => { register_a: 0 },
+        set: [Zero],
+    );
+
+    test_opcode!(
+        test_0xa9_lda_negative_flag,
+        [0xA9, 0x80, 0x00],
+        => { register_a: 0x80 },
+        set: [Negative],
+        clear: [Zero],
+    );
 
     #[test]
-    fn test_0xa9_lda_immediate_load_data() {
+    fn test_lda_from_memory() {
         let mut cpu = Cpu::default();
-        cpu.load_and_run(vec![0xA9, 0x05, 0x00]);
-        dbg!(&cpu.status);
+        cpu.mem_write(0x10, 0x55);
 
-        assert_eq!(cpu.register_a, 0x05);
-        assert!(!cpu.status.contains(CpuFlags::Zero));
-        assert!(!cpu.status.contains(CpuFlags::Negative));
+        cpu.load_and_run(vec![0xa5, 0x10, 0x00]);
+
+        assert_eq!(cpu.register_a, 0x55);
     }
 
+    test_opcode!(
+        test_0xaa_tax_move_a_to_x,
+        [0xAA, 0x00],
+        { register_a: 10 } => { register_x: 10 },
+    );
+
+    test_opcode!(
+        test_0x0a_asl_accumulator,
+        [0xA9, 0x81, 0x0A, 0x00],
+        => { register_a: 0x02 },
+        set: [CarryBit],
+        clear: [Zero, Negative],
+    );
+
     #[test]
-    fn test_0xa9_lda_zero_flag() {
+    fn test_5_ops_working_together() {
         let mut cpu = Cpu::default();
-        cpu.load_and_run(vec![0xa9, 0x00, 0x00]);
+        cpu.load_and_run(vec![0xA9, 0xC0, 0xAA, 0xE8, 0x00]);
 
-        assert_eq!(cpu.register_a, 0);
-        assert!(cpu.status.contains(CpuFlags::Zero));
+        assert_eq!(cpu.register_x, 0xc1)
     }
 
     #[test]
-    fn test_lda_from_memory() {
+    fn test_inx_overflow() {
         let mut cpu = Cpu::default();
-        cpu.mem_write(0x10, 0x55);
+        cpu.load(vec![0xE8, 0xE8, 0x00]);
+        cpu.reset();
+        cpu.register_x = u8::MAX;
+        cpu.run();
 
-        cpu.load_and_run(vec![0xa5, 0x10, 0x00]);
+        assert_eq!(cpu.register_x, 1)
+    }
 
-        assert_eq!(cpu.register_a, 0x55);
+    #[test]
+    fn test_next_pc_skips_operands() {
+        let mut cpu = Cpu::default();
+        cpu.load(vec![0xA9, 0x05, 0x00]);
+        cpu.reset();
+
+        assert_eq!(cpu.next_pc(), cpu.program_counter + 2);
     }
 
     #[test]
-    fn test_0xaa_tax_move_a_to_x() {
+    fn test_run_until_cycle_stops_on_instruction_boundary() {
         let mut cpu = Cpu::default();
-        cpu.load(vec![0xAA, 0x00]);
+        cpu.load(vec![0xA9, 0x05, 0xAA, 0xE8, 0xE8, 0x00]);
         cpu.reset();
-        cpu.register_a = 10;
-        cpu.run();
 
-        assert_eq!(cpu.register_x, 10)
+        assert!(matches!(
+            cpu.run_until_cycle(RESET_CYCLES + 5),
+            RunResult::Running
+        ));
+        assert_eq!(cpu.cycles, RESET_CYCLES + 6);
+        assert_eq!(cpu.program_counter, GAME_START_ADDRESS + 4);
     }
 
     #[test]
-    fn test_5_ops_working_together() {
+    fn test_zero_page_x_wraps_within_zero_page() {
         let mut cpu = Cpu::default();
-        cpu.load_and_run(vec![0xA9, 0xC0, 0xAA, 0xE8, 0x00]);
+        cpu.mem_write(0x0000, 0x42);
+        cpu.mem_write(0x0100, 0x99);
+        cpu.load(vec![0xB5, 0xFF, 0x00]);
+        cpu.reset();
+        cpu.register_x = 1;
+        cpu.run();
 
-        assert_eq!(cpu.register_x, 0xc1)
+        assert_eq!(cpu.register_a, 0x42);
     }
 
     #[test]
-    fn test_inx_overflow() {
+    fn test_zero_page_y_wraps_within_zero_page() {
         let mut cpu = Cpu::default();
-        cpu.load(vec![0xE8, 0xE8, 0x00]);
+        cpu.mem_write(0x0000, 0x42);
+        cpu.mem_write(0x0100, 0x99);
+        cpu.load(vec![0xB6, 0xFF, 0x00]);
         cpu.reset();
-        cpu.register_x = u8::MAX;
+        cpu.register_y = 1;
         cpu.run();
 
-        assert_eq!(cpu.register_x, 1)
+        assert_eq!(cpu.register_x, 0x42);
     }
 
     #[test]
-    fn test_snake() {
+    fn test_custom_handler_runs_for_unused_opcode() {
         let mut cpu = Cpu::default();
-        cpu.load_and_run(super::super::SNAKE.to_vec());
+        cpu.register_handler(0x02, Box::new(|cpu: &mut Cpu, _| cpu.register_a = 0x42));
+        cpu.load_and_run(vec![0x02, 0x00]);
+
+        assert_eq!(cpu.register_a, 0x42);
+    }
+
+    // The NES' 2A03 has no BCD circuitry: the decimal flag can be set but ADC/SBC ignore it.
+    #[test]
+    fn test_adc_ignores_decimal_mode() {
+        let mut cpu = Cpu::default();
+        cpu.load_and_run(vec![0xF8, 0xA9, 0x79, 0x69, 0x01, 0x00]);
+
+        assert_eq!(cpu.register_a, 0x7A);
+        assert!(cpu.status.contains(CpuFlags::DecimalMode));
+        assert!(!cpu.status.contains(CpuFlags::Negative));
+        assert!(!cpu.status.contains(CpuFlags::Overflow));
+    }
+
+    #[test]
+    fn test_sparse_memory_resolves_operand_address() {
+        let mut memory = memory::SparseMemory::default();
+        memory.mem_write(0x0600, 0x10);
+        memory.mem_write(0x0010, 0x34);
+        memory.mem_write(0x0011, 0x12);
+
+        assert_eq!(memory.mem_read(0x4242), 0);
+        assert_eq!(
+            memory::operand_address(&memory, AddressingMode::IndirectY, 0x0600, 0, 0x01),
+            Some(0x1235)
+        );
+        assert_eq!(
+            memory::operand_address(&memory, AddressingMode::NoneAddressing, 0x0600, 0, 0),
+            None
+        );
+    }
+
+    #[test]
+    fn test_is_halted_after_brk() {
+        let mut cpu = Cpu::default();
+        cpu.load(vec![0xA9, 0x05, 0x00]);
+        cpu.reset();
+        assert!(!cpu.is_halted());
+
+        cpu.run();
+        assert!(cpu.is_halted());
+
+        cpu.reset();
+        assert!(!cpu.is_halted());
+    }
+
+    #[test]
+    fn test_opcode_table_has_no_duplicate_codes() {
+        let mut seen = std::collections::HashSet::new();
+        for opcode in opcode::CPU_OPS_CODES.iter() {
+            assert!(
+                seen.insert(opcode.code),
+                "Opcode 0x{:X?} appears twice in the table",
+                opcode.code
+            );
+        }
     }
 
-    // #[test]
-    // fn test_branch_timings() {
-    //     let mut cpu = Cpu::default();
-    //     let bytes = include_bytes!("../../branch_timing_tests/1.Branch_Basics.nes").to_vec();
-    //     cpu.load(bytes);
-    //     cpu.reset();
-    //
-    //     cpu.run_single_cycle();
-    // }
+    #[rustfmt::skip]
+    const OFFICIAL_OPCODES: [u8; 151] = [
+        0x00, 0x01, 0x05, 0x06, 0x08, 0x09, 0x0A, 0x0D, 0x0E,
+        0x10, 0x11, 0x15, 0x16, 0x18, 0x19, 0x1D, 0x1E,
+        0x20, 0x21, 0x24, 0x25, 0x26, 0x28, 0x29, 0x2A, 0x2C, 0x2D, 0x2E,
+        0x30, 0x31, 0x35, 0x36, 0x38, 0x39, 0x3D, 0x3E,
+        0x40, 0x41, 0x45, 0x46, 0x48, 0x49, 0x4A, 0x4C, 0x4D, 0x4E,
+        0x50, 0x51, 0x55, 0x56, 0x58, 0x59, 0x5D, 0x5E,
+        0x60, 0x61, 0x65, 0x66, 0x68, 0x69, 0x6A, 0x6C, 0x6D, 0x6E,
+        0x70, 0x71, 0x75, 0x76, 0x78, 0x79, 0x7D, 0x7E,
+        0x81, 0x84, 0x85, 0x86, 0x88, 0x8A, 0x8C, 0x8D, 0x8E,
+        0x90, 0x91, 0x94, 0x95, 0x96, 0x98, 0x99, 0x9A, 0x9D,
+        0xA0, 0xA1, 0xA2, 0xA4, 0xA5, 0xA6, 0xA8, 0xA9, 0xAA, 0xAC, 0xAD, 0xAE,
+        0xB0, 0xB1, 0xB4, 0xB5, 0xB6, 0xB8, 0xB9, 0xBA, 0xBC, 0xBD, 0xBE,
+        0xC0, 0xC1, 0xC4, 0xC5, 0xC6, 0xC8, 0xC9, 0xCA, 0xCC, 0xCD, 0xCE,
+        0xD0, 0xD1, 0xD5, 0xD6, 0xD8, 0xD9, 0xDD, 0xDE,
+        0xE0, 0xE1, 0xE4, 0xE5, 0xE6, 0xE8, 0xE9, 0xEA, 0xEC, 0xED, 0xEE,
+        0xF0, 0xF1, 0xF5, 0xF6, 0xF8, 0xF9, 0xFD, 0xFE,
+    ];
+    const UNOFFICIAL_OPCODES: [u8; 5] = [0x0B, 0x2B, 0x4B, 0x6B, 0xCB];
+
+    #[test]
+    fn test_opcode_count() {
+        assert_eq!(
+            opcode::opcode_count(),
+            OFFICIAL_OPCODES.len() + UNOFFICIAL_OPCODES.len()
+        );
+    }
+
+    #[test]
+    fn test_every_byte_decodes_or_is_illegal() {
+        for code in 0..=u8::MAX {
+            let official = OFFICIAL_OPCODES.contains(&code);
+            let unofficial = UNOFFICIAL_OPCODES.contains(&code);
+
+            assert_eq!(
+                OPCODES_MAP.contains_key(&code),
+                official || unofficial,
+                "Opcode 0x{:02X} (official: {})",
+                code,
+                official
+            );
+        }
+    }
+
+    #[test]
+    fn test_php_plp_round_trip() {
+        let mut cpu = Cpu::default();
+        cpu.load_and_run(vec![
+            0x38, 0xF8, 0x78, // SEC, SED, SEI
+            0x08, // PHP
+            0x18, 0xD8, 0x58, 0xA9, 0x00, // CLC, CLD, CLI, LDA #$00
+            0x28, // PLP
+            0x00,
+        ]);
+
+        // PHP pushes Break and the unused bit along with the live flags
+        assert_eq!(cpu.mem_read(0x0100), 0b0011_1101);
+        assert_eq!(
+            cpu.status,
+            CpuFlags::CarryBit
+                | CpuFlags::DecimalMode
+                | CpuFlags::DisableInterrupts
+                | CpuFlags::_Unused
+        );
+    }
+
+    #[test]
+    fn test_with_memory_preloads_cells() {
+        let mut cpu = Cpu::with_memory(&[(0x10, 0x55), (0x11, 0x66)]);
+        cpu.load_and_run(vec![0xa5, 0x10, 0x00]);
+
+        assert_eq!(cpu.register_a, 0x55);
+        assert_eq!(cpu.mem_read(0x11), 0x66);
+    }
+
+    #[test]
+    fn test_listing_of_snake_prefix() {
+        let mut cpu = Cpu::default();
+        cpu.load(super::super::SNAKE.to_vec());
+
+        let listing = cpu.listing(0x0600, 0x0611);
+        let lines = listing.lines().collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            vec![
+                "0600  20 06 06  JSR $0606",
+                "0603  20 38 06  JSR $0638",
+                "0606  20 0D 06  JSR $060D",
+                "0609  20 2A 06  JSR $062A",
+                "060C  60        RTS",
+                "060D  A9 02     LDA #$02",
+                "060F  85 02     STA $02",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_relocated_stack_page() {
+        let mut cpu = Cpu::default();
+        cpu.set_stack_page(0x0300);
+        cpu.load(vec![0x48, 0x00]);
+        cpu.reset();
+        cpu.register_a = 0x42;
+        cpu.run();
+
+        assert_eq!(cpu.mem_read(0x0300), 0x42);
+        assert_eq!(cpu.mem_read(0x0100), 0x00);
+    }
+
+    #[test]
+    fn test_program_range_matches_loaded_program() {
+        let mut cpu = Cpu::default();
+        cpu.load(super::super::SNAKE.to_vec());
+
+        assert_eq!(
+            cpu.program_range(),
+            GAME_START_ADDRESS..=GAME_START_ADDRESS + 308
+        );
+    }
+
+    #[test]
+    fn test_program_range_up_to_end_of_memory() {
+        let mut cpu = Cpu::default();
+        cpu.load(vec![0xea; 0x10000 - GAME_START_ADDRESS as usize]);
+
+        assert_eq!(cpu.program_range(), GAME_START_ADDRESS..=0xFFFF);
+
+        cpu.set_program_guard(ProgramGuard::Stop);
+        cpu.reset();
+        assert_eq!(cpu.run_single_cycle(), RunResult::Running);
+    }
+
+    #[test]
+    fn test_reset_vector_pointing_to_top_of_memory() {
+        let mut cpu = Cpu::default();
+        cpu.mem_write(0xFFF0, 0xA9);
+        cpu.mem_write(0xFFF1, 0x42);
+        cpu.mem_write(0xFFF2, 0x00);
+        cpu.mem_write_u16(RESET_ADDRESS, 0xFFF0);
+        cpu.mem_write_u16(0xFFFE, 0x1234);
+
+        cpu.reset();
+        assert_eq!(cpu.program_counter, 0xFFF0);
+
+        cpu.run();
+        assert_eq!(cpu.register_a, 0x42);
+        assert_eq!(cpu.mem_read_u16(0xFFFE), 0x1234);
+    }
+
+    #[test]
+    fn test_stop_on_brk_halts() {
+        let mut cpu = Cpu::default();
+        cpu.load_and_run(vec![0xE8, 0x00, 0xE8]);
+
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.register_x, 1);
+    }
+
+    #[test]
+    fn test_continuous_mode_runs_through_brk() {
+        let mut cpu = Cpu::default();
+        cpu.set_run_mode(RunMode::Continuous);
+        cpu.load(vec![0xE8, 0x00, 0x00]);
+        cpu.mem_write_u16(IRQ_ADDRESS, GAME_START_ADDRESS);
+        cpu.reset();
+
+        assert!(matches!(cpu.run_until_cycle(100), RunResult::Running));
+        assert!(!cpu.is_halted());
+        assert!(cpu.register_x > 1);
+    }
+
+    #[test]
+    fn test_mem_read_range() {
+        let mut cpu = Cpu::default();
+        cpu.load(super::super::SNAKE.to_vec());
+
+        let block = cpu.mem_read_range(GAME_START_ADDRESS, 16);
+        let bytes = (0..16)
+            .map(|offset| cpu.mem_read(GAME_START_ADDRESS + offset))
+            .collect::<Vec<_>>();
+
+        assert_eq!(block, bytes);
+        assert_eq!(&block[..3], &[0x20, 0x06, 0x06]);
+    }
+
+    #[test]
+    fn test_mem_read_range_wraps() {
+        let cpu = Cpu::with_memory(&[(0xFFFF, 0x12), (0x0000, 0x34)]);
+
+        assert_eq!(cpu.mem_read_range(0xFFFF, 2), vec![0x12, 0x34]);
+    }
+
+    #[test]
+    fn test_cmp_register_lower_than_operand() {
+        let mut cpu = Cpu::default();
+        cpu.load_and_run(vec![0xA9, 0x10, 0xC9, 0x20, 0x00]);
+
+        assert!(!cpu.status.contains(CpuFlags::CarryBit));
+        assert!(!cpu.status.contains(CpuFlags::Zero));
+        assert!(cpu.status.contains(CpuFlags::Negative));
+    }
+
+    #[test]
+    fn test_cmp_register_equal_to_operand() {
+        let mut cpu = Cpu::default();
+        cpu.load_and_run(vec![0xA9, 0x20, 0xC9, 0x20, 0x00]);
+
+        assert!(cpu.status.contains(CpuFlags::CarryBit));
+        assert!(cpu.status.contains(CpuFlags::Zero));
+        assert!(!cpu.status.contains(CpuFlags::Negative));
+    }
+
+    #[test]
+    fn test_trace_run_yields_executed_instructions() {
+        let mut cpu = Cpu::default();
+        cpu.load(vec![0xA9, 0xC0, 0xAA, 0xE8, 0x00]);
+        cpu.reset();
+
+        let entries = cpu.trace_run(10).collect::<Vec<_>>();
+        let pcs = entries.iter().map(|entry| entry.pc).collect::<Vec<_>>();
+        let reprs = entries
+            .iter()
+            .map(|entry| entry.opcode.unwrap().repr)
+            .collect::<Vec<_>>();
+
+        assert_eq!(pcs, vec![0x0600, 0x0602, 0x0603, 0x0604]);
+        assert_eq!(reprs, vec!["LDA", "TAX", "INX", "BRK"]);
+        assert_eq!(entries[2].state.register_x, 0xC0);
+        assert_eq!(cpu.register_x, 0xC1);
+    }
+
+    #[test]
+    fn test_trace_run_through_unknown_opcodes() {
+        let mut cpu = Cpu::default();
+        // 0x02 runs through a handler, 0x12 is skipped in lenient mode
+        cpu.load(vec![0x02, 0x12, 0xE8, 0x00]);
+        cpu.reset();
+        cpu.set_lenient(true);
+        cpu.register_handler(0x02, Box::new(|cpu, _| cpu.register_x = 0x10));
+
+        let entries = cpu.trace_run(10).collect::<Vec<_>>();
+        let codes = entries
+            .iter()
+            .map(|entry| (entry.pc, entry.code, entry.opcode.map(|opcode| opcode.repr)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            codes,
+            vec![
+                (0x0600, 0x02, None),
+                (0x0601, 0x12, None),
+                (0x0602, 0xE8, Some("INX")),
+                (0x0603, 0x00, Some("BRK")),
+            ]
+        );
+        assert_eq!(cpu.register_x, 0x11);
+    }
+
+    #[test]
+    fn test_illegal_opcode_returns_an_error() {
+        let mut cpu = Cpu::default();
+        cpu.load(vec![0xE8, 0x02, 0x00]);
+        cpu.reset();
+
+        assert_eq!(cpu.run_single_cycle(), RunResult::Running);
+        assert_eq!(
+            cpu.run_single_cycle(),
+            RunResult::Error(CpuError::IllegalOpcode {
+                opcode: 0x02,
+                address: 0x0601
+            })
+        );
+        assert_eq!(cpu.program_counter, 0x0601);
+        assert!(cpu.is_halted());
+    }
+
+    #[test]
+    fn test_anc_copies_negative_into_carry() {
+        let mut cpu = Cpu::default();
+        cpu.load_and_run(vec![0xA9, 0xFF, 0x0B, 0x80, 0x00]);
+
+        assert_eq!(cpu.register_a, 0x80);
+        assert!(cpu.status.contains(CpuFlags::CarryBit));
+        assert!(cpu.status.contains(CpuFlags::Negative));
+    }
+
+    #[test]
+    fn test_alr_ands_then_shifts_right() {
+        let mut cpu = Cpu::default();
+        cpu.load_and_run(vec![0xA9, 0xFF, 0x4B, 0x03, 0x00]);
+
+        assert_eq!(cpu.register_a, 0x01);
+        assert!(cpu.status.contains(CpuFlags::CarryBit));
+    }
+
+    #[test]
+    fn test_arr_sets_carry_and_overflow_from_result() {
+        let mut cpu = Cpu::default();
+        // SEC, LDA #$FF, ARR #$80: (0x80 >> 1) | carry = 0xC0
+        cpu.load_and_run(vec![0x38, 0xA9, 0xFF, 0x6B, 0x80, 0x00]);
+
+        assert_eq!(cpu.register_a, 0xC0);
+        assert!(cpu.status.contains(CpuFlags::CarryBit));
+        assert!(cpu.status.contains(CpuFlags::Overflow));
+        assert!(cpu.status.contains(CpuFlags::Negative));
+    }
+
+    #[test]
+    fn test_axs_subtracts_from_a_and_x() {
+        let mut cpu = Cpu::default();
+        // LDA #$F0, LDX #$3C, AXS #$10: (0xF0 & 0x3C) - 0x10 = 0x20
+        cpu.load_and_run(vec![0xA9, 0xF0, 0xA2, 0x3C, 0xCB, 0x10, 0x00]);
+
+        assert_eq!(cpu.register_x, 0x20);
+        assert!(cpu.status.contains(CpuFlags::CarryBit));
+        assert!(!cpu.status.contains(CpuFlags::Zero));
+    }
+
+    #[test]
+    fn test_load_image_uses_its_own_reset_vector() {
+        let mut image = vec![0; MEMORY_SIZE];
+        image[0x8000..0x8003].copy_from_slice(&[0xA9, 0x42, 0x00]);
+        image[RESET_ADDRESS as usize] = 0x00;
+        image[RESET_ADDRESS as usize + 1] = 0x80;
+
+        let mut cpu = Cpu::default();
+        cpu.load_image(&image);
+        cpu.reset();
+        assert_eq!(cpu.program_counter, 0x8000);
+
+        cpu.run();
+        assert_eq!(cpu.register_a, 0x42);
+    }
+
+    #[test]
+    fn test_operand_bytes_of_absolute_instruction() {
+        let mut cpu = Cpu::default();
+        cpu.load(vec![0xAD, 0x34, 0x12, 0x00]);
+        cpu.reset();
+
+        let opcode = OPCODES_MAP[&0xAD];
+        assert_eq!(cpu.operand_bytes(opcode), vec![0x34, 0x12]);
+    }
+
+    #[test]
+    fn test_rng_seed_survives_reset() {
+        let run = || {
+            let mut cpu = Cpu::default();
+            cpu.set_rng_seed(0x6502);
+            cpu.load(super::super::SNAKE.to_vec());
+            cpu.reset();
+            for _ in 0..100 {
+                cpu.refresh_rng();
+                cpu.run_single_cycle();
+            }
+
+            cpu.reset();
+            for _ in 0..500 {
+                cpu.refresh_rng();
+                cpu.run_single_cycle();
+            }
+
+            (cpu.state(), cpu.mem_read_range(0, 0x0600))
+        };
+
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn test_reset_restarts_rng_sequence() {
+        let mut cpu = Cpu::default();
+        cpu.set_rng_seed(7);
+        let draw = |cpu: &mut Cpu| {
+            (0..8)
+                .map(|_| {
+                    cpu.refresh_rng();
+                    cpu.mem_read(crate::RNG_ADDRESS.into())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let first = draw(&mut cpu);
+        cpu.reset();
+        assert_eq!(draw(&mut cpu), first);
+    }
+
+    #[test]
+    fn test_same_program_yields_equal_cpus() {
+        let run = || {
+            let mut cpu = Cpu::default();
+            cpu.load_and_run(vec![0xA9, 0xC0, 0xAA, 0xE8, 0x85, 0x10, 0x00]);
+            cpu
+        };
+
+        let mut cpu = run();
+        assert_eq!(cpu, run());
+
+        cpu.mem_write(0x10, 0x00);
+        assert_ne!(cpu, run());
+    }
+
+    fn pushed_status_byte(cpu: &Cpu) -> u8 {
+        cpu.mem_read(stack::STACK + cpu.stack_pointer as u16 + 1)
+    }
+
+    #[test]
+    fn test_pushed_status_bits() {
+        let mut cpu = Cpu {
+            stack_pointer: 0xFD,
+            ..Default::default()
+        };
+        cpu.php();
+        assert_eq!(pushed_status_byte(&cpu) & 0b0011_0000, 0b0011_0000);
+
+        let mut cpu = Cpu {
+            stack_pointer: 0xFD,
+            ..Default::default()
+        };
+        cpu.interrupt_nmi();
+        assert_eq!(pushed_status_byte(&cpu) & 0b0011_0000, 0b0010_0000);
+        assert!(cpu.status.contains(CpuFlags::DisableInterrupts));
+
+        let mut cpu = Cpu {
+            stack_pointer: 0xFD,
+            status: CpuFlags::Break.into(),
+            ..Default::default()
+        };
+        cpu.interrupt_irq();
+        assert_eq!(pushed_status_byte(&cpu) & 0b0011_0000, 0b0010_0000);
+
+        let mut cpu = Cpu::default();
+        cpu.set_run_mode(RunMode::Continuous);
+        cpu.load(vec![0x00, 0x00]);
+        cpu.reset();
+        cpu.stack_pointer = 0xFD;
+        cpu.run_single_cycle();
+        assert_eq!(pushed_status_byte(&cpu) & 0b0011_0000, 0b0011_0000);
+    }
+
+    #[test]
+    fn test_irq_is_masked_by_disable_interrupts() {
+        let mut cpu = Cpu {
+            stack_pointer: 0xFD,
+            status: CpuFlags::DisableInterrupts.into(),
+            ..Default::default()
+        };
+        cpu.interrupt_irq();
+
+        assert_eq!(cpu.stack_pointer, 0xFD);
+        assert_eq!(cpu.cycles, 0);
+    }
+
+    #[test]
+    fn test_map_io_intercepts_accesses() {
+        use memory::IoHandler;
+        use std::rc::Rc;
+
+        // (address, written value)
+        type Accesses = Rc<RefCell<Vec<(u16, Option<u8>)>>>;
+        struct Logger(Accesses);
+
+        impl IoHandler for Logger {
+            fn read(&mut self, addr: u16) -> u8 {
+                self.0.borrow_mut().push((addr, None));
+                0x42
+            }
+
+            fn write(&mut self, addr: u16, data: u8) {
+                self.0.borrow_mut().push((addr, Some(data)));
+            }
+        }
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut cpu = Cpu::default();
+        cpu.map_io(0x4000..=0x4017, Box::new(Logger(Rc::clone(&log))));
+        cpu.load(vec![0xAD, 0x02, 0x40, 0x8D, 0x17, 0x40, 0x00]);
+        cpu.reset();
+        cpu.run();
+
+        assert_eq!(cpu.register_a, 0x42);
+        assert_eq!(*log.borrow(), vec![(0x4002, None), (0x4017, Some(0x42))]);
+        assert_eq!(cpu.memory[0x4017], 0);
+
+        cpu.mem_write(0x4018, 0x01);
+        assert_eq!(cpu.mem_read(0x4018), 0x01);
+        assert_eq!(log.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_stack_dump() {
+        let mut cpu = Cpu {
+            stack_pointer: 0xFF,
+            ..Default::default()
+        };
+        assert!(cpu.stack_dump().is_empty());
+
+        cpu.stack_push(0x01);
+        cpu.stack_push(0x02);
+        cpu.stack_push_u16(0x0634);
+
+        assert_eq!(cpu.stack_dump(), vec![0x34, 0x06, 0x02, 0x01]);
+    }
+
+    #[test]
+    fn test_jmp_indirect() {
+        let mut cpu = Cpu::default();
+        cpu.load(vec![0x6C, 0x00, 0x30]);
+        cpu.mem_write_u16(0x3000, 0x1234);
+        cpu.reset();
+        cpu.run_single_cycle();
+
+        assert_eq!(cpu.program_counter, 0x1234);
+    }
+
+    #[test]
+    fn test_run_from() {
+        let mut cpu = Cpu::with_memory(&[(0x0700, 0xA9), (0x0701, 0x42), (0x0702, 0xAA)]);
+        cpu.run_from(0x0700);
+
+        assert_eq!(cpu.register_a, 0x42);
+        assert_eq!(cpu.register_x, 0x42);
+        assert!(cpu.is_halted());
+    }
+
+    #[test]
+    fn test_adc_sbc_overflow() {
+        // (opcode, a, operand, result, overflow), ADC with carry clear and SBC with carry set
+        let cases = [
+            (0x69, 0x50, 0x50, 0xA0, true),
+            (0x69, 0xD0, 0x90, 0x60, true),
+            (0x69, 0x50, 0x90, 0xE0, false),
+            (0x69, 0xD0, 0x10, 0xE0, false),
+            (0xE9, 0x50, 0xB0, 0xA0, true),
+            (0xE9, 0xD0, 0x70, 0x60, true),
+            (0xE9, 0x50, 0x10, 0x40, false),
+            (0xE9, 0xD0, 0xF0, 0xE0, false),
+        ];
+
+        for (code, a, operand, result, overflow) in cases {
+            let carry = if code == 0xE9 { 0x38 } else { 0x18 };
+            let mut cpu = Cpu::default();
+            cpu.load_and_run(vec![carry, 0xA9, a, code, operand, 0x00]);
+
+            let case = format!(
+                "0x{:02X} with A=0x{:02X}, operand 0x{:02X}",
+                code, a, operand
+            );
+            assert_eq!(cpu.register_a, result, "{}", case);
+            assert_eq!(
+                cpu.status.contains(CpuFlags::Overflow),
+                overflow,
+                "{}",
+                case
+            );
+        }
+    }
+
+    #[test]
+    fn test_last_instruction_cycles() {
+        let mut cpu = Cpu::default();
+        // LDX #$01; LDA $02FF,X; LDA $0200,X
+        cpu.load(vec![0xA2, 0x01, 0xBD, 0xFF, 0x02, 0xBD, 0x00, 0x02, 0x00]);
+        cpu.reset();
+
+        cpu.run_single_cycle();
+        assert_eq!(cpu.last_instruction_cycles(), 2);
+        cpu.run_single_cycle();
+        assert_eq!(cpu.last_instruction_cycles(), 5);
+        cpu.run_single_cycle();
+        assert_eq!(cpu.last_instruction_cycles(), 4);
+    }
+
+    #[test]
+    fn test_branch_cycles() {
+        let mut cpu = Cpu::default();
+        // LDX #$02; loop: DEX; BNE loop
+        cpu.load(vec![0xA2, 0x02, 0xCA, 0xD0, 0xFD, 0x00]);
+        cpu.reset();
+
+        cpu.run_single_cycle();
+        cpu.run_single_cycle();
+        cpu.run_single_cycle();
+        assert_eq!(cpu.last_instruction_cycles(), 3);
+        cpu.run_single_cycle();
+        cpu.run_single_cycle();
+        assert_eq!(cpu.last_instruction_cycles(), 2);
+    }
+
+    #[test]
+    fn test_find_brk() {
+        let mut cpu = Cpu::default();
+        cpu.load(super::super::SNAKE.to_vec());
+
+        let range = cpu.program_range();
+        assert_eq!(cpu.find_brk(*range.start()), Some(range.end() + 1));
+
+        cpu.load(vec![0xA9, 0x00, 0x00]);
+        assert_eq!(
+            cpu.find_brk(GAME_START_ADDRESS),
+            Some(GAME_START_ADDRESS + 2)
+        );
+
+        let cpu = Cpu::with_memory(&[(0xFFFE, 0xEA), (0xFFFF, 0xEA)]);
+        assert_eq!(cpu.find_brk(0xFFFE), None);
+    }
+
+    #[test]
+    fn test_flag_delta() {
+        let mut cpu = Cpu::default();
+        cpu.load(vec![0xA9, 0x01, 0xE9, 0x01, 0x00]);
+        cpu.reset();
+        cpu.status.insert(CpuFlags::CarryBit);
+        cpu.run_single_cycle();
+
+        let before = cpu.state();
+        cpu.run_single_cycle();
+
+        assert_eq!(
+            before.flag_delta(&cpu.state()),
+            vec![(CpuFlags::Zero, true)]
+        );
+    }
+
+    #[test]
+    fn test_run_summary() {
+        let mut cpu = Cpu::default();
+        cpu.load_and_run(vec![0xA9, 0x05, 0xAA, 0xE8, 0x00]);
+
+        let summary = cpu.run_summary();
+        assert_eq!(summary.instructions, 4);
+        assert_eq!(summary.cycles, RESET_CYCLES + 2 + 2 + 2 + 7);
+        assert_eq!(summary.result, RunResult::Done);
+        assert_eq!(
+            summary.to_string(),
+            "4 instructions in 20 cycles, A=$05 X=$06 Y=$00 P=$00 SP=$00 PC=$0605, halted on BRK"
+        );
+    }
+
+    #[test]
+    fn test_brk_return_address_skips_padding_byte() {
+        let mut cpu = Cpu::default();
+        cpu.set_run_mode(RunMode::Continuous);
+        cpu.load(vec![0x00, 0xFF]);
+        cpu.mem_write_u16(IRQ_ADDRESS, 0x0700);
+        cpu.reset();
+        cpu.stack_pointer = 0xFF;
+        cpu.run_single_cycle();
+
+        let stack = cpu.stack_dump();
+        assert_eq!(
+            u16::from_le_bytes([stack[1], stack[2]]),
+            GAME_START_ADDRESS + 2
+        );
+        assert_eq!(cpu.program_counter, 0x0700);
+    }
+
+    #[test]
+    fn test_control_flow_trace() {
+        let mut cpu = Cpu::default();
+        // LDX #$02; loop: DEX; BNE loop; JMP end; end: BRK
+        cpu.load(vec![0xA2, 0x02, 0xCA, 0xD0, 0xFD, 0x4C, 0x08, 0x06, 0x00]);
+        cpu.reset();
+
+        let trace = cpu
+            .control_flow_trace(100)
+            .into_iter()
+            .map(|entry| (entry.pc, entry.opcode.repr, entry.target))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            trace,
+            vec![
+                (0x0603, "BNE", 0x0602),
+                (0x0603, "BNE", 0x0605),
+                (0x0605, "JMP", 0x0608),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_control_flow_trace_through_unknown_opcodes() {
+        let mut cpu = Cpu::default();
+        // 0x02 (handler); 0x12 (lenient NOP); JMP end; end: BRK
+        cpu.load(vec![0x02, 0x12, 0x4C, 0x05, 0x06, 0x00]);
+        cpu.reset();
+        cpu.set_lenient(true);
+        cpu.register_handler(0x02, Box::new(|_, _| {}));
+
+        let trace = cpu
+            .control_flow_trace(100)
+            .into_iter()
+            .map(|entry| (entry.pc, entry.opcode.repr, entry.target))
+            .collect::<Vec<_>>();
+
+        assert_eq!(trace, vec![(0x0602, "JMP", 0x0605)]);
+    }
+
+    #[test]
+    fn test_reset_takes_7_cycles() {
+        let mut cpu = Cpu::default();
+        cpu.load(vec![0xE8, 0x00]);
+        cpu.reset();
+        assert_eq!(cpu.cycles, 7);
+
+        cpu.run_single_cycle();
+        cpu.reset();
+        assert_eq!(cpu.cycles, 7);
+    }
+
+    #[test]
+    fn test_strict_mode_reports_uninitialized_reads() {
+        let mut cpu = Cpu::default();
+        // STA $10; LDA $10; LDA $20
+        cpu.load(vec![0x85, 0x10, 0xA5, 0x10, 0xA5, 0x20, 0x00]);
+        cpu.reset();
+        cpu.set_strict(true);
+
+        assert_eq!(cpu.run_single_cycle(), RunResult::Running);
+        assert_eq!(cpu.run_single_cycle(), RunResult::Running);
+        assert_eq!(
+            cpu.run_single_cycle(),
+            RunResult::Error(CpuError::UninitializedRead(0x20))
+        );
+        assert!(cpu.is_halted());
+    }
+
+    #[test]
+    fn test_uninitialized_reads_are_allowed_by_default() {
+        let mut cpu = Cpu::default();
+        cpu.load_and_run(vec![0xA5, 0x20, 0x00]);
+
+        assert_eq!(cpu.run_summary().result, RunResult::Done);
+    }
+
+    #[test]
+    fn test_zero_page() {
+        let mut cpu = Cpu::default();
+        cpu.mem_write(0x00, 0x12);
+        cpu.mem_write(0xFF, 0x34);
+        cpu.mem_write(0x100, 0x56);
+
+        let zero_page = cpu.zero_page();
+        assert_eq!(zero_page[0x00], 0x12);
+        assert_eq!(zero_page[0xFF], 0x34);
+        assert_eq!(zero_page.iter().filter(|&&byte| byte != 0).count(), 2);
+    }
+
+    #[test]
+    fn test_wait_for_result() {
+        use conformance::TestOutcome;
+
+        // LDA #$01; CMP #$01; BNE fail; pass: JMP pass; fail: JMP fail
+        let program = vec![
+            0xA9, 0x01, 0xC9, 0x01, 0xD0, 0x03, 0x4C, 0x06, 0x06, 0x4C, 0x09, 0x06,
+        ];
+        let mut cpu = Cpu::default();
+        cpu.load(program.clone());
+        cpu.reset();
+        assert_eq!(cpu.wait_for_result(0x0606, 0x0609, 1000), TestOutcome::Pass);
+
+        cpu.load(program);
+        cpu.mem_write(0x0603, 0x02);
+        cpu.reset();
+        assert_eq!(cpu.wait_for_result(0x0606, 0x0609, 1000), TestOutcome::Fail);
+
+        cpu.reset();
+        assert_eq!(
+            cpu.wait_for_result(0x0700, 0x0701, 1000),
+            TestOutcome::Timeout
+        );
+    }
+
+    #[test]
+    fn test_custom_input_address() {
+        let mut cpu = Cpu::default();
+        assert_eq!(cpu.input_address(), 0xFF);
+
+        cpu.set_input_address(0x4016);
+        cpu.load(vec![0xAD, 0x16, 0x40, 0x00]);
+        cpu.reset();
+        cpu.set_input(0x77);
+        cpu.run();
+
+        assert_eq!(cpu.register_a, 0x77);
+        assert_eq!(cpu.mem_read(0xFF), 0);
+    }
+
+    #[test]
+    fn test_save_and_restore_registers() {
+        let mut cpu = Cpu::default();
+        cpu.load(vec![0xA9, 0x80, 0x85, 0x10, 0x00]);
+        cpu.reset();
+
+        let registers = cpu.save_registers();
+        cpu.run();
+        cpu.restore_registers(registers);
+
+        assert_eq!(cpu.save_registers(), registers);
+        assert_eq!(cpu.register_a, 0);
+        assert!(!cpu.status.contains(CpuFlags::Negative));
+        assert_eq!(cpu.mem_read(0x10), 0x80);
+    }
+
+    #[test]
+    fn test_run_cycles() {
+        let mut cpu = Cpu::default();
+        cpu.load(vec![0xE8, 0xE8, 0xE8, 0x00]);
+        cpu.reset();
+
+        assert_eq!(cpu.run_cycles(3), 4);
+        assert_eq!(cpu.register_x, 2);
+        assert_eq!(cpu.run_cycles(100), 2 + 7);
+        assert!(cpu.is_halted());
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut cpu = Cpu::default();
+        // LDA #$01; STA $10; INC $10; STA $11
+        cpu.load(vec![0xA9, 0x01, 0x85, 0x10, 0xE6, 0x10, 0x85, 0x11, 0x00]);
+        cpu.reset();
+        cpu.set_history_depth(3);
+        assert!(!cpu.step_back());
+
+        cpu.run_single_cycle();
+        let snapshot = cpu.state();
+        let memory = cpu.memory.clone();
+        cpu.run_single_cycle();
+        cpu.run_single_cycle();
+        cpu.run_single_cycle();
+        assert_eq!(cpu.mem_read(0x10), 0x02);
+
+        assert!(cpu.step_back());
+        assert!(cpu.step_back());
+        assert!(cpu.step_back());
+        assert!(!cpu.step_back());
+        assert_eq!(cpu.state(), snapshot);
+        assert!(cpu.memory == memory);
+        assert_eq!(cpu.mem_read(0x10), 0x00);
+    }
+
+    #[test]
+    fn test_disassemble_indexed_indirect_and_indirect_indexed() {
+        let cpu = Cpu::with_memory(&[(0x00, 0xA1), (0x01, 0x10), (0x02, 0xB1), (0x03, 0x10)]);
+
+        assert_eq!(cpu.disassemble(0x00), "LDA ($10,X)");
+        assert_eq!(cpu.disassemble(0x02), "LDA ($10),Y");
+    }
+
+    #[test]
+    fn test_preloaded_stack_for_rts_and_rti() {
+        let mut cpu = Cpu::default();
+        cpu.load(vec![0x60]);
+        cpu.reset();
+        cpu.stack_pointer = 0xFF;
+        cpu.push_return_address(0x0700);
+        cpu.run_single_cycle();
+
+        assert_eq!(cpu.program_counter, 0x0701);
+        assert_eq!(cpu.stack_pointer, 0xFF);
+
+        cpu.load(vec![0x40]);
+        cpu.reset();
+        cpu.push_return_address(0x0700);
+        cpu.push_status(0b1100_0011);
+        cpu.run_single_cycle();
+
+        assert_eq!(cpu.program_counter, 0x0700);
+        assert_eq!(cpu.status.bits(), 0b1110_0011);
+    }
+
+    #[test]
+    fn test_opcode_table_matches_map() {
+        for code in 0..=u8::MAX {
+            assert_eq!(
+                opcode::OPCODES_TABLE[code as usize].map(|opcode| opcode.code),
+                OPCODES_MAP.get(&code).map(|opcode| opcode.code)
+            );
+            assert_eq!(
+                opcode::decode(code).map(|opcode| opcode.code),
+                OPCODES_MAP.get(&code).map(|opcode| opcode.code)
+            );
+        }
+    }
+
+    #[test]
+    fn test_lenient_mode_skips_unknown_opcodes() {
+        let mut cpu = Cpu::default();
+        cpu.set_lenient(true);
+        // LAX $10 (unofficial, not in the table); NOP $1234,X; INX
+        cpu.load_and_run(vec![0xA7, 0x10, 0x1C, 0x34, 0x12, 0xE8, 0x00]);
+
+        assert_eq!(cpu.register_x, 1);
+        assert_eq!(cpu.run_summary().result, RunResult::Done);
+    }
+
+    #[test]
+    fn test_encoded_len_matches_table() {
+        for opcode in opcode::CPU_OPS_CODES.iter() {
+            assert_eq!(
+                opcode::encoded_len(opcode.code),
+                opcode.len,
+                "0x{:02X}",
+                opcode.code
+            );
+        }
+    }
+
+    #[test]
+    fn test_current_addressing_mode() {
+        let mut cpu = Cpu::default();
+        cpu.load(vec![0xA9, 0x05, 0xB5, 0x10, 0x02]);
+        cpu.reset();
+
+        assert_eq!(
+            cpu.current_addressing_mode(),
+            Some(AddressingMode::Immediate)
+        );
+        cpu.run_single_cycle();
+        assert_eq!(
+            cpu.current_addressing_mode(),
+            Some(AddressingMode::ZeroPageX)
+        );
+        cpu.run_single_cycle();
+        assert_eq!(cpu.current_addressing_mode(), None);
+    }
+
+    #[test]
+    fn test_absolute_operand_wraps_at_end_of_memory() {
+        let mut cpu = Cpu::with_memory(&[
+            (0xFFFE, 0xAD),
+            (0xFFFF, 0x34),
+            (0x0000, 0x12),
+            (0x1234, 0x42),
+        ]);
+        cpu.program_counter = 0xFFFF;
+        assert_eq!(cpu.get_operand_address(AddressingMode::Absolute), 0x1234);
+
+        cpu.run_from(0xFFFE);
+        assert_eq!(cpu.register_a, 0x42);
+        assert_eq!(cpu.run_summary().instructions, 2);
+    }
+
+    #[test]
+    fn test_mem_write_u16_wraps() {
+        let mut cpu = Cpu::default();
+        cpu.mem_write_u16(0xFFFF, 0x1234);
+
+        assert_eq!(cpu.mem_read(0xFFFF), 0x34);
+        assert_eq!(cpu.mem_read(0x0000), 0x12);
+        assert_eq!(cpu.mem_read_u16(0xFFFF), 0x1234);
+    }
+
+    #[test]
+    fn test_last_branch_taken() {
+        let mut cpu = Cpu::default();
+        // LDA #$00; BEQ +0; LDA #$01; BEQ +0
+        cpu.load(vec![0xA9, 0x00, 0xF0, 0x00, 0xA9, 0x01, 0xF0, 0x00, 0x00]);
+        cpu.reset();
+        assert_eq!(cpu.last_branch_taken(), None);
+
+        cpu.run_single_cycle();
+        cpu.run_single_cycle();
+        assert_eq!(cpu.last_branch_taken(), Some(true));
+
+        cpu.run_single_cycle();
+        assert_eq!(cpu.last_branch_taken(), Some(true));
+        cpu.run_single_cycle();
+        assert_eq!(cpu.last_branch_taken(), Some(false));
+    }
+
+    #[test]
+    fn test_power_on_ram_pattern() {
+        let cases = [
+            (RamPattern::AllZero, [0x00, 0x00, 0x00, 0x00]),
+            (RamPattern::AllOnes, [0xFF, 0xFF, 0xFF, 0xFF]),
+            (RamPattern::Alternating, [0x00, 0xFF, 0x00, 0xFF]),
+        ];
+
+        for (pattern, expected) in cases {
+            let mut cpu = Cpu::default();
+            cpu.mem_write(0x0800, 0x42);
+            cpu.set_power_on_ram_pattern(pattern);
+            cpu.power_on();
+
+            assert_eq!(cpu.mem_read_range(0x0000, 4), expected, "{:?}", pattern);
+            assert_eq!(cpu.mem_read_range(0x07FC, 4), expected, "{:?}", pattern);
+            assert_eq!(cpu.mem_read(0x0800), 0x42);
+        }
+    }
+
+    #[test]
+    fn test_instruction_len_at() {
+        let cpu = Cpu::with_memory(&[(0x00, 0xA9), (0x01, 0x05), (0x02, 0x00), (0x03, 0x02)]);
+
+        assert_eq!(cpu.instruction_len_at(0x00), 2);
+        assert_eq!(cpu.instruction_len_at(0x02), 1);
+        assert_eq!(cpu.instruction_len_at(0x03), 1);
+    }
+
+    #[test]
+    fn test_call_depth() {
+        let mut cpu = Cpu::default();
+        // JSR sub; BRK; sub: JSR leaf; RTS; leaf: RTS
+        cpu.load(vec![0x20, 0x04, 0x06, 0x00, 0x20, 0x08, 0x06, 0x60, 0x60]);
+        cpu.reset();
+
+        cpu.run_single_cycle();
+        cpu.run_single_cycle();
+        assert_eq!(cpu.call_depth(), 2);
+        cpu.run_single_cycle();
+        cpu.run_single_cycle();
+        assert_eq!(cpu.call_depth(), 0);
+    }
+
+    #[test]
+    fn test_program_guard_stops_at_program_boundary() {
+        let mut cpu = Cpu::with_memory(&[(0x0602, 0xE8), (0x0603, 0xE8)]);
+        cpu.set_program_guard(ProgramGuard::Stop);
+        cpu.load_and_run(vec![0xE8, 0xE8]);
+
+        assert_eq!(cpu.register_x, 2);
+        assert_eq!(cpu.program_counter, 0x0602);
+        assert_eq!(cpu.run_summary().result, RunResult::Done);
+        assert_eq!(cpu.run_summary().instructions, 2);
+
+        cpu.set_program_guard(ProgramGuard::Off);
+        cpu.reset();
+        cpu.run();
+        assert_eq!(cpu.register_x, 4);
+    }
+
+    #[test]
+    fn test_decode_at() {
+        use disassembler::Operand;
+
+        let cpu = Cpu::with_memory(&[(0x0600, 0xBD), (0x0601, 0x34), (0x0602, 0x12)]);
+        let instruction = cpu.decode_at(0x0600).unwrap();
+
+        assert_eq!(instruction.address, 0x0600);
+        assert_eq!(instruction.opcode.repr, "LDA");
+        assert_eq!(instruction.opcode.code, 0xBD);
+        assert_eq!(instruction.operand, Operand::AbsoluteX(0x1234));
+        assert_eq!(instruction.bytes, vec![0xBD, 0x34, 0x12]);
+
+        let cpu = Cpu::with_memory(&[(0x0600, 0xD0), (0x0601, 0xFC), (0x0602, 0x02)]);
+        let instruction = cpu.decode_at(0x0600).unwrap();
+        assert_eq!(instruction.operand, Operand::Relative(-4));
+        assert_eq!(instruction.branch_target(), Some(0x05FE));
+        assert!(cpu.decode_at(0x0602).is_none());
+    }
+
+    #[test]
+    fn test_vectors() {
+        let mut cpu = Cpu::default();
+        cpu.mem_write_u16(0xFFFA, 0x1234);
+        cpu.mem_write_u16(0xFFFC, 0x8000);
+        cpu.mem_write_u16(0xFFFE, 0xC0DE);
+
+        assert_eq!(
+            cpu.vectors(),
+            state::InterruptVectors {
+                nmi: 0x1234,
+                reset: 0x8000,
+                irq: 0xC0DE,
+            }
+        );
+    }
+
+    #[test]
+    fn test_clone_runs_independently() {
+        let mut cpu = Cpu::default();
+        // LDA #$01; STA $10; INC $10; INC $10; BRK
+        cpu.load(vec![0xa9, 0x01, 0x85, 0x10, 0xe6, 0x10, 0xe6, 0x10, 0x00]);
+        cpu.reset();
+        cpu.run_single_cycle();
+        cpu.run_single_cycle();
+
+        let snapshot = cpu.clone();
+        let mut clone = cpu.clone();
+        assert_eq!(clone, cpu);
+
+        clone.run();
+        assert_eq!(clone.mem_read(0x10), 0x03);
+        assert!(clone.is_halted());
+
+        assert_eq!(cpu, snapshot);
+        assert_eq!(cpu.mem_read(0x10), 0x01);
+        assert_eq!(cpu.program_counter, 0x0604);
+        assert!(!cpu.is_halted());
+    }
+
+    #[test]
+    fn test_trace_sink() {
+        use std::rc::Rc;
+
+        struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+        impl Write for SharedBuffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let mut cpu = Cpu::default();
+        cpu.load(vec![0xa9, 0x05, 0xaa, 0x00]);
+        cpu.reset();
+        cpu.set_trace_sink(Box::new(SharedBuffer(buffer.clone())));
+        cpu.run();
+
+        let trace = String::from_utf8(buffer.borrow().clone()).unwrap();
+        let lines = trace.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(
+            lines[0].starts_with("0600  A9 05     LDA #$05"),
+            "{}",
+            lines[0]
+        );
+        assert!(
+            lines[0].ends_with("A:00 X:00 Y:00 P:00 SP:00 CYC:7"),
+            "{}",
+            lines[0]
+        );
+        assert!(lines[1].starts_with("0602  AA        TAX"), "{}", lines[1]);
+        assert!(lines[1].contains("A:05"), "{}", lines[1]);
+        assert!(lines[2].starts_with("0603  00        BRK"), "{}", lines[2]);
+    }
+
+    #[test]
+    fn test_step_with_rng_is_deterministic() {
+        let seeded = || {
+            let mut cpu = Cpu::default();
+            cpu.set_rng_seed(42);
+            cpu.load(crate::SNAKE.to_vec());
+            cpu.reset();
+            cpu
+        };
+        let mut first = seeded();
+        let mut second = seeded();
+
+        for _ in 0..2_000 {
+            assert_eq!(first.step_with_rng(), second.step_with_rng());
+            assert_eq!(first, second);
+        }
+        assert_ne!(first.mem_read(crate::RNG_ADDRESS.into()), 0);
+    }
+
+    #[test]
+    fn test_load_image_clears_program_range() {
+        let mut cpu = Cpu::default();
+        cpu.load(vec![0xea, 0xea, 0x00]);
+        cpu.load_image(&[0x00]);
+
+        assert!(cpu.program_range().is_empty());
+    }
+
+    #[test]
+    fn test_jsr_at_end_of_memory_wraps_return_address() {
+        // JSR $0700 straddling 0xFFFE-0x0000, RTS at 0x0700
+        let mut cpu = Cpu::with_memory(&[
+            (0xFFFE, 0x20),
+            (0xFFFF, 0x00),
+            (0x0000, 0x07),
+            (0x0700, 0x60),
+        ]);
+        cpu.stack_pointer = 0xFD;
+        cpu.program_counter = 0xFFFE;
+
+        cpu.run_single_cycle();
+        assert_eq!(cpu.program_counter, 0x0700);
+        assert_eq!(cpu.stack_dump()[..2], [0x00, 0x00]);
+
+        cpu.run_single_cycle();
+        assert_eq!(cpu.program_counter, 0x0001);
+    }
+
+    #[test]
+    fn test_rts_to_0xffff_wraps() {
+        let mut cpu = Cpu::with_memory(&[(0x0600, 0x60)]);
+        cpu.stack_pointer = 0xFD;
+        cpu.program_counter = 0x0600;
+        cpu.push_return_address(0xFFFF);
+
+        cpu.run_single_cycle();
+        assert_eq!(cpu.program_counter, 0x0000);
+    }
+
+    #[test]
+    fn test_illegal_opcode_at_end_of_memory() {
+        let mut cpu = Cpu::with_memory(&[(0xFFFF, 0x02)]);
+        cpu.program_counter = 0xFFFF;
+
+        assert_eq!(
+            cpu.run_single_cycle(),
+            RunResult::Error(CpuError::IllegalOpcode {
+                opcode: 0x02,
+                address: 0xFFFF,
+            })
+        );
+        assert_eq!(cpu.program_counter, 0xFFFF);
+    }
+
+    #[test]
+    fn test_stack_dump_on_last_page() {
+        let mut cpu = Cpu::default();
+        cpu.set_stack_page(0xFF00);
+        cpu.stack_pointer = 0xFF;
+        assert!(cpu.stack_dump().is_empty());
+
+        cpu.mem_write(0xFFFF, 0x42);
+        cpu.stack_pointer = 0xFE;
+        assert_eq!(cpu.stack_dump(), vec![0x42]);
+    }
+
+    #[test]
+    fn test_stack_on_last_page() {
+        let mut cpu = Cpu::default();
+        cpu.set_stack_page(0xFF00);
+        cpu.stack_pointer = 0xFF;
+
+        cpu.stack_push_u16(0x1234);
+        assert_eq!(cpu.mem_read_u16(0xFFFE), 0x1234);
+        assert_eq!(cpu.stack_pop_u16(), 0x1234);
+        assert_eq!(cpu.stack_pointer, 0xFF);
+    }
+
+    #[test]
+    #[should_panic(expected = "not page aligned")]
+    fn test_unaligned_stack_page() {
+        Cpu::default().set_stack_page(0x0180);
+    }
+
+    #[test]
+    fn test_inspection_does_not_clock_mapped_io() {
+        use crate::joypad::{Joypad, JoypadButton, JOYPAD_1_ADDRESS};
+        use std::rc::Rc;
+
+        let joypad = Rc::new(RefCell::new(Joypad::default()));
+        joypad.borrow_mut().set_pressed(JoypadButton::B, true);
+
+        // LDA $4016
+        let mut cpu = Cpu::with_memory(&[(0x0600, 0xAD), (0x0601, 0x16), (0x0602, 0x40)]);
+        cpu.map_io(
+            JOYPAD_1_ADDRESS..=JOYPAD_1_ADDRESS,
+            Box::new(joypad.clone()),
+        );
+        cpu.program_counter = 0x0600;
+        cpu.mem_write(JOYPAD_1_ADDRESS, 1);
+        cpu.mem_write(JOYPAD_1_ADDRESS, 0);
+
+        cpu.disassemble(JOYPAD_1_ADDRESS);
+        cpu.decode_at(JOYPAD_1_ADDRESS);
+        cpu.listing(0x4000, 0x4020);
+        cpu.trace_line();
+        cpu.mem_read_range(JOYPAD_1_ADDRESS, 4);
+        cpu.set_stack_page(0x4000);
+        cpu.stack_pointer = 0x10;
+        cpu.stack_dump();
+
+        // Still on the first button (A, released) then B
+        assert_eq!(cpu.mem_read(JOYPAD_1_ADDRESS), 0);
+        assert_eq!(cpu.mem_read(JOYPAD_1_ADDRESS), 1);
+    }
+
+    #[test]
+    fn test_step_back_restores_bookkeeping() {
+        let mut cpu = Cpu::default();
+        // JSR sub; sub: LDX #$00; BEQ next; next: INX
+        cpu.load(vec![0x20, 0x03, 0x06, 0xA2, 0x00, 0xF0, 0x00, 0xE8]);
+        cpu.reset();
+        cpu.stack_pointer = 0xFD;
+        cpu.set_history_depth(8);
+
+        cpu.run_single_cycle();
+        cpu.run_single_cycle();
+        let (depth, taken, cycles) = (
+            cpu.call_depth(),
+            cpu.last_branch_taken(),
+            cpu.last_instruction_cycles(),
+        );
+        cpu.run_single_cycle();
+        assert_eq!(cpu.last_branch_taken(), Some(true));
+
+        assert!(cpu.step_back());
+        assert_eq!(cpu.call_depth(), depth);
+        assert_eq!(cpu.last_branch_taken(), taken);
+        assert_eq!(cpu.last_instruction_cycles(), cycles);
+
+        assert!(cpu.step_back());
+        assert!(cpu.step_back());
+        assert_eq!(cpu.call_depth(), 0);
+    }
+
+    #[test]
+    fn test_failed_instructions_are_not_recorded() {
+        let mut cpu = Cpu::default();
+        cpu.load(vec![0xE8, 0x02]);
+        cpu.reset();
+        cpu.set_history_depth(1);
+
+        cpu.run_single_cycle();
+        assert!(matches!(cpu.run_single_cycle(), RunResult::Error(_)));
+
+        // The INX is still there to undo
+        assert!(cpu.step_back());
+        assert_eq!(cpu.register_x, 0);
+        assert_eq!(cpu.program_counter, 0x0600);
+        assert!(!cpu.step_back());
+    }
+
+    #[test]
+    fn test_strict_mode_sees_writes_made_before_enabling_it() {
+        let mut cpu = Cpu::with_memory(&[(0x20, 0x05)]);
+        // LDA $20
+        cpu.load(vec![0xA5, 0x20, 0x00]);
+        cpu.reset();
+        cpu.set_strict(true);
+
+        assert_eq!(cpu.run_single_cycle(), RunResult::Running);
+        assert_eq!(cpu.register_a, 0x05);
+    }
+
+    #[test]
+    fn test_step_back_after_uninitialized_read() {
+        let mut cpu = Cpu::default();
+        // INX; INC $30
+        cpu.load(vec![0xE8, 0xE6, 0x30, 0x00]);
+        cpu.reset();
+        cpu.set_strict(true);
+        cpu.set_history_depth(4);
+
+        cpu.run_single_cycle();
+        assert_eq!(
+            cpu.run_single_cycle(),
+            RunResult::Error(CpuError::UninitializedRead(0x30))
+        );
+        assert_eq!(cpu.mem_read(0x30), 0x01);
+        assert_eq!(cpu.run_summary().instructions, 2);
+
+        assert!(cpu.step_back());
+        assert_eq!(cpu.peek(0x30), 0x00);
+        assert_eq!(cpu.register_x, 1);
+        assert_eq!(cpu.program_counter, 0x0601);
+    }
+
+    #[test]
+    fn test_run_cycles_with_rng_matches_stepping() {
+        let seeded = || {
+            let mut cpu = Cpu::default();
+            cpu.set_rng_seed(7);
+            cpu.load(crate::SNAKE.to_vec());
+            cpu.reset();
+            cpu
+        };
+        let mut chunked = seeded();
+        let mut stepped = seeded();
+
+        let cycles = chunked.run_cycles_with_rng(1_000);
+        assert!(cycles >= 1_000);
+        while stepped.cycles < chunked.cycles {
+            stepped.step_with_rng();
+        }
+        assert_eq!(chunked, stepped);
+    }
+
+    #[test]
+    fn test_handler_for_unknown_opcode_takes_cycles() {
+        let mut cpu = Cpu::default();
+        // 0x02 jumping back to itself forever
+        cpu.load(vec![0x02]);
+        cpu.reset();
+        cpu.register_handler(0x02, Box::new(|cpu, _| cpu.program_counter = 0x0600));
+
+        assert!(cpu.run_cycles(10) >= 10);
+        assert_eq!(cpu.last_instruction_cycles(), 2);
+    }
+
+    #[test]
+    fn test_ldx_reads_mapped_io_once() {
+        use crate::joypad::{Joypad, JoypadButton, JOYPAD_1_ADDRESS};
+        use std::rc::Rc;
+
+        let joypad = Rc::new(RefCell::new(Joypad::default()));
+        joypad.borrow_mut().set_pressed(JoypadButton::B, true);
+
+        let mut cpu = Cpu::default();
+        // LDX $4016; LDY $4016
+        cpu.load(vec![0xAE, 0x16, 0x40, 0xAC, 0x16, 0x40, 0x00]);
+        cpu.reset();
+        cpu.map_io(JOYPAD_1_ADDRESS..=JOYPAD_1_ADDRESS, Box::new(joypad));
+        cpu.run();
+
+        // A then B
+        assert_eq!(cpu.register_x, 0);
+        assert_eq!(cpu.register_y, 1);
+    }
+
+    #[test]
+    fn test_snake() {
+        let mut cpu = Cpu::default();
+        cpu.load_and_run(super::super::SNAKE.to_vec());
+    }
+
+    /// Load an iNES test ROM, `name` being relative to the crate root, mapped like an NROM
+    /// cartridge: PRG-ROM at `0x8000`, mirrored at `0xC000` when there is a single 16KB bank.
+    /// The CPU is reset and ready to run.
+    fn load_test_rom(name: &str) -> Cpu {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(name);
+        let rom = std::fs::read(&path)
+            .unwrap_or_else(|error| panic!("Could not read {}: {}", path.display(), error));
+        assert_eq!(&rom[..4], b"NES\x1A", "{} is not an iNES file", name);
+
+        let prg_size = rom[4] as usize * 0x4000;
+        let prg = &rom[16..16 + prg_size];
+        let mut image = vec![0; MEMORY_SIZE];
+        for bank in (0x8000..MEMORY_SIZE).step_by(prg_size) {
+            image[bank..bank + prg_size].copy_from_slice(prg);
+        }
+
+        let mut cpu = Cpu::default();
+        cpu.load_image(&image);
+        cpu.reset();
+
+        cpu
+    }
+
+    #[test]
+    fn test_load_branch_timing_rom() {
+        let mut cpu = load_test_rom("branch_timing_tests/1.Branch_Basics.nes");
+        assert_eq!(cpu.program_counter, 0xE124);
+        assert_eq!(cpu.disassemble(cpu.program_counter), "JSR $E0BB");
+
+        cpu.run_single_cycle();
+        assert_eq!(cpu.program_counter, 0xE0BB);
+    }
 
     #[ctor::ctor]
     fn init() {
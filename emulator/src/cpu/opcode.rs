@@ -28,202 +28,234 @@ impl std::fmt::Debug for OpCode {
     }
 }
 
+/// Number of opcodes the CPU can decode, unofficial ones included.
+pub fn opcode_count() -> usize {
+    CPU_OPS_CODES.len()
+}
+
+/// Length of any opcode, table or not, derived from the addressing mode its column encodes.
+/// `BRK` and the `KIL` opcodes count as a single byte.
+pub fn encoded_len(code: u8) -> u8 {
+    match code & 0x1F {
+        0x02 if code >= 0x80 => 2,
+        0x02 | 0x12 | 0x08 | 0x0A | 0x18 | 0x1A => 1,
+        0x0C..=0x0F | 0x19 | 0x1B..=0x1F => 3,
+        0x00 if code == 0x20 => 3,
+        0x00 if code == 0x00 || code == 0x40 || code == 0x60 => 1,
+        _ => 2,
+    }
+}
+
+/// Build the opcode table from `(code, repr, len, cycles, mode)` tuples.
+macro_rules! opcodes {
+    ($(($code:expr, $repr:expr, $len:expr, $cycles:expr, $mode:ident)),* $(,)?) => {
+        vec![$(OpCode::new($code, $repr, $len, $cycles, AddressingMode::$mode)),*]
+    };
+}
+
 // Adapted from https://www.nesdev.org/obelisk-6502-guide/reference.html
 lazy_static::lazy_static! {
-    pub static ref CPU_OPS_CODES: Vec<OpCode> = vec![
-        OpCode::new(0x00, "BRK", 1, 7, AddressingMode::NoneAddressing),
-        OpCode::new(0xea, "NOP", 1, 2, AddressingMode::NoneAddressing),
+    pub static ref CPU_OPS_CODES: Vec<OpCode> = opcodes![
+        (0x00, "BRK", 1, 7, NoneAddressing),
+        (0xea, "NOP", 1, 2, NoneAddressing),
 
         /* Arithmetic */
-        OpCode::new(0x69, "ADC", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0x65, "ADC", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x75, "ADC", 2, 4, AddressingMode::ZeroPageX),
-        OpCode::new(0x6d, "ADC", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0x7d, "ADC", 3, 4/*+1 if page crossed*/, AddressingMode::AbsoluteX),
-        OpCode::new(0x79, "ADC", 3, 4/*+1 if page crossed*/, AddressingMode::AbsoluteY),
-        OpCode::new(0x61, "ADC", 2, 6, AddressingMode::IndirectX),
-        OpCode::new(0x71, "ADC", 2, 5/*+1 if page crossed*/, AddressingMode::IndirectY),
-
-        OpCode::new(0xe9, "SBC", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0xe5, "SBC", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0xf5, "SBC", 2, 4, AddressingMode::ZeroPageX),
-        OpCode::new(0xed, "SBC", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0xfd, "SBC", 3, 4/*+1 if page crossed*/, AddressingMode::AbsoluteX),
-        OpCode::new(0xf9, "SBC", 3, 4/*+1 if page crossed*/, AddressingMode::AbsoluteY),
-        OpCode::new(0xe1, "SBC", 2, 6, AddressingMode::IndirectX),
-        OpCode::new(0xf1, "SBC", 2, 5/*+1 if page crossed*/, AddressingMode::IndirectY),
-
-        OpCode::new(0x29, "AND", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0x25, "AND", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x35, "AND", 2, 4, AddressingMode::ZeroPageX),
-        OpCode::new(0x2d, "AND", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0x3d, "AND", 3, 4/*+1 if page crossed*/, AddressingMode::AbsoluteX),
-        OpCode::new(0x39, "AND", 3, 4/*+1 if page crossed*/, AddressingMode::AbsoluteY),
-        OpCode::new(0x21, "AND", 2, 6, AddressingMode::IndirectX),
-        OpCode::new(0x31, "AND", 2, 5/*+1 if page crossed*/, AddressingMode::IndirectY),
-
-        OpCode::new(0x49, "EOR", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0x45, "EOR", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x55, "EOR", 2, 4, AddressingMode::ZeroPageX),
-        OpCode::new(0x4d, "EOR", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0x5d, "EOR", 3, 4/*+1 if page crossed*/, AddressingMode::AbsoluteX),
-        OpCode::new(0x59, "EOR", 3, 4/*+1 if page crossed*/, AddressingMode::AbsoluteY),
-        OpCode::new(0x41, "EOR", 2, 6, AddressingMode::IndirectX),
-        OpCode::new(0x51, "EOR", 2, 5/*+1 if page crossed*/, AddressingMode::IndirectY),
-
-        OpCode::new(0x09, "ORA", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0x05, "ORA", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x15, "ORA", 2, 4, AddressingMode::ZeroPageX),
-        OpCode::new(0x0d, "ORA", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0x1d, "ORA", 3, 4/*+1 if page crossed*/, AddressingMode::AbsoluteX),
-        OpCode::new(0x19, "ORA", 3, 4/*+1 if page crossed*/, AddressingMode::AbsoluteY),
-        OpCode::new(0x01, "ORA", 2, 6, AddressingMode::IndirectX),
-        OpCode::new(0x11, "ORA", 2, 5/*+1 if page crossed*/, AddressingMode::IndirectY),
+        (0x69, "ADC", 2, 2, Immediate),
+        (0x65, "ADC", 2, 3, ZeroPage),
+        (0x75, "ADC", 2, 4, ZeroPageX),
+        (0x6d, "ADC", 3, 4, Absolute),
+        (0x7d, "ADC", 3, 4/*+1 if page crossed*/, AbsoluteX),
+        (0x79, "ADC", 3, 4/*+1 if page crossed*/, AbsoluteY),
+        (0x61, "ADC", 2, 6, IndirectX),
+        (0x71, "ADC", 2, 5/*+1 if page crossed*/, IndirectY),
+
+        (0xe9, "SBC", 2, 2, Immediate),
+        (0xe5, "SBC", 2, 3, ZeroPage),
+        (0xf5, "SBC", 2, 4, ZeroPageX),
+        (0xed, "SBC", 3, 4, Absolute),
+        (0xfd, "SBC", 3, 4/*+1 if page crossed*/, AbsoluteX),
+        (0xf9, "SBC", 3, 4/*+1 if page crossed*/, AbsoluteY),
+        (0xe1, "SBC", 2, 6, IndirectX),
+        (0xf1, "SBC", 2, 5/*+1 if page crossed*/, IndirectY),
+
+        (0x29, "AND", 2, 2, Immediate),
+        (0x25, "AND", 2, 3, ZeroPage),
+        (0x35, "AND", 2, 4, ZeroPageX),
+        (0x2d, "AND", 3, 4, Absolute),
+        (0x3d, "AND", 3, 4/*+1 if page crossed*/, AbsoluteX),
+        (0x39, "AND", 3, 4/*+1 if page crossed*/, AbsoluteY),
+        (0x21, "AND", 2, 6, IndirectX),
+        (0x31, "AND", 2, 5/*+1 if page crossed*/, IndirectY),
+
+        (0x49, "EOR", 2, 2, Immediate),
+        (0x45, "EOR", 2, 3, ZeroPage),
+        (0x55, "EOR", 2, 4, ZeroPageX),
+        (0x4d, "EOR", 3, 4, Absolute),
+        (0x5d, "EOR", 3, 4/*+1 if page crossed*/, AbsoluteX),
+        (0x59, "EOR", 3, 4/*+1 if page crossed*/, AbsoluteY),
+        (0x41, "EOR", 2, 6, IndirectX),
+        (0x51, "EOR", 2, 5/*+1 if page crossed*/, IndirectY),
+
+        (0x09, "ORA", 2, 2, Immediate),
+        (0x05, "ORA", 2, 3, ZeroPage),
+        (0x15, "ORA", 2, 4, ZeroPageX),
+        (0x0d, "ORA", 3, 4, Absolute),
+        (0x1d, "ORA", 3, 4/*+1 if page crossed*/, AbsoluteX),
+        (0x19, "ORA", 3, 4/*+1 if page crossed*/, AbsoluteY),
+        (0x01, "ORA", 2, 6, IndirectX),
+        (0x11, "ORA", 2, 5/*+1 if page crossed*/, IndirectY),
 
         /* Shifts */
-        OpCode::new(0x0a, "ASL", 1, 2, AddressingMode::NoneAddressing),
-        OpCode::new(0x06, "ASL", 2, 5, AddressingMode::ZeroPage),
-        OpCode::new(0x16, "ASL", 2, 6, AddressingMode::ZeroPageX),
-        OpCode::new(0x0e, "ASL", 3, 6, AddressingMode::Absolute),
-        OpCode::new(0x1e, "ASL", 3, 7, AddressingMode::AbsoluteX),
-
-        OpCode::new(0x4a, "LSR", 1, 2, AddressingMode::NoneAddressing),
-        OpCode::new(0x46, "LSR", 2, 5, AddressingMode::ZeroPage),
-        OpCode::new(0x56, "LSR", 2, 6, AddressingMode::ZeroPageX),
-        OpCode::new(0x4e, "LSR", 3, 6, AddressingMode::Absolute),
-        OpCode::new(0x5e, "LSR", 3, 7, AddressingMode::AbsoluteX),
-
-        OpCode::new(0x2a, "ROL", 1, 2, AddressingMode::NoneAddressing),
-        OpCode::new(0x26, "ROL", 2, 5, AddressingMode::ZeroPage),
-        OpCode::new(0x36, "ROL", 2, 6, AddressingMode::ZeroPageX),
-        OpCode::new(0x2e, "ROL", 3, 6, AddressingMode::Absolute),
-        OpCode::new(0x3e, "ROL", 3, 7, AddressingMode::AbsoluteX),
-
-        OpCode::new(0x6a, "ROR", 1, 2, AddressingMode::NoneAddressing),
-        OpCode::new(0x66, "ROR", 2, 5, AddressingMode::ZeroPage),
-        OpCode::new(0x76, "ROR", 2, 6, AddressingMode::ZeroPageX),
-        OpCode::new(0x6e, "ROR", 3, 6, AddressingMode::Absolute),
-        OpCode::new(0x7e, "ROR", 3, 7, AddressingMode::AbsoluteX),
-
-        OpCode::new(0xe6, "INC", 2, 5, AddressingMode::ZeroPage),
-        OpCode::new(0xf6, "INC", 2, 6, AddressingMode::ZeroPageX),
-        OpCode::new(0xee, "INC", 3, 6, AddressingMode::Absolute),
-        OpCode::new(0xfe, "INC", 3, 7, AddressingMode::AbsoluteX),
-
-        OpCode::new(0xe8, "INX", 1, 2, AddressingMode::NoneAddressing),
-        OpCode::new(0xc8, "INY", 1, 2, AddressingMode::NoneAddressing),
-
-        OpCode::new(0xc6, "DEC", 2, 5, AddressingMode::ZeroPage),
-        OpCode::new(0xd6, "DEC", 2, 6, AddressingMode::ZeroPageX),
-        OpCode::new(0xce, "DEC", 3, 6, AddressingMode::Absolute),
-        OpCode::new(0xde, "DEC", 3, 7, AddressingMode::AbsoluteX),
-
-        OpCode::new(0xca, "DEX", 1, 2, AddressingMode::NoneAddressing),
-        OpCode::new(0x88, "DEY", 1, 2, AddressingMode::NoneAddressing),
-
-        OpCode::new(0xc9, "CMP", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0xc5, "CMP", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0xd5, "CMP", 2, 4, AddressingMode::ZeroPageX),
-        OpCode::new(0xcd, "CMP", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0xdd, "CMP", 3, 4/*+1 if page crossed*/, AddressingMode::AbsoluteX),
-        OpCode::new(0xd9, "CMP", 3, 4/*+1 if page crossed*/, AddressingMode::AbsoluteY),
-        OpCode::new(0xc1, "CMP", 2, 6, AddressingMode::IndirectX),
-        OpCode::new(0xd1, "CMP", 2, 5/*+1 if page crossed*/, AddressingMode::IndirectY),
-
-        OpCode::new(0xc0, "CPY", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0xc4, "CPY", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0xcc, "CPY", 3, 4, AddressingMode::Absolute),
-
-        OpCode::new(0xe0, "CPX", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0xe4, "CPX", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0xec, "CPX", 3, 4, AddressingMode::Absolute),
+        (0x0a, "ASL", 1, 2, NoneAddressing),
+        (0x06, "ASL", 2, 5, ZeroPage),
+        (0x16, "ASL", 2, 6, ZeroPageX),
+        (0x0e, "ASL", 3, 6, Absolute),
+        (0x1e, "ASL", 3, 7, AbsoluteX),
+
+        (0x4a, "LSR", 1, 2, NoneAddressing),
+        (0x46, "LSR", 2, 5, ZeroPage),
+        (0x56, "LSR", 2, 6, ZeroPageX),
+        (0x4e, "LSR", 3, 6, Absolute),
+        (0x5e, "LSR", 3, 7, AbsoluteX),
+
+        (0x2a, "ROL", 1, 2, NoneAddressing),
+        (0x26, "ROL", 2, 5, ZeroPage),
+        (0x36, "ROL", 2, 6, ZeroPageX),
+        (0x2e, "ROL", 3, 6, Absolute),
+        (0x3e, "ROL", 3, 7, AbsoluteX),
+
+        (0x6a, "ROR", 1, 2, NoneAddressing),
+        (0x66, "ROR", 2, 5, ZeroPage),
+        (0x76, "ROR", 2, 6, ZeroPageX),
+        (0x6e, "ROR", 3, 6, Absolute),
+        (0x7e, "ROR", 3, 7, AbsoluteX),
+
+        (0xe6, "INC", 2, 5, ZeroPage),
+        (0xf6, "INC", 2, 6, ZeroPageX),
+        (0xee, "INC", 3, 6, Absolute),
+        (0xfe, "INC", 3, 7, AbsoluteX),
+
+        (0xe8, "INX", 1, 2, NoneAddressing),
+        (0xc8, "INY", 1, 2, NoneAddressing),
+
+        (0xc6, "DEC", 2, 5, ZeroPage),
+        (0xd6, "DEC", 2, 6, ZeroPageX),
+        (0xce, "DEC", 3, 6, Absolute),
+        (0xde, "DEC", 3, 7, AbsoluteX),
+
+        (0xca, "DEX", 1, 2, NoneAddressing),
+        (0x88, "DEY", 1, 2, NoneAddressing),
+
+        (0xc9, "CMP", 2, 2, Immediate),
+        (0xc5, "CMP", 2, 3, ZeroPage),
+        (0xd5, "CMP", 2, 4, ZeroPageX),
+        (0xcd, "CMP", 3, 4, Absolute),
+        (0xdd, "CMP", 3, 4/*+1 if page crossed*/, AbsoluteX),
+        (0xd9, "CMP", 3, 4/*+1 if page crossed*/, AbsoluteY),
+        (0xc1, "CMP", 2, 6, IndirectX),
+        (0xd1, "CMP", 2, 5/*+1 if page crossed*/, IndirectY),
+
+        (0xc0, "CPY", 2, 2, Immediate),
+        (0xc4, "CPY", 2, 3, ZeroPage),
+        (0xcc, "CPY", 3, 4, Absolute),
+
+        (0xe0, "CPX", 2, 2, Immediate),
+        (0xe4, "CPX", 2, 3, ZeroPage),
+        (0xec, "CPX", 3, 4, Absolute),
 
 
         /* Branching */
 
-        OpCode::new(0x4c, "JMP", 3, 3, AddressingMode::NoneAddressing), //AddressingMode that acts as Immidiate
-        OpCode::new(0x6c, "JMP", 3, 5, AddressingMode::NoneAddressing), //AddressingMode:Indirect with 6502 bug
+        (0x4c, "JMP", 3, 3, NoneAddressing), //AddressingMode that acts as Immidiate
+        (0x6c, "JMP", 3, 5, NoneAddressing), //AddressingMode:Indirect with 6502 bug
 
-        OpCode::new(0x20, "JSR", 3, 6, AddressingMode::NoneAddressing),
-        OpCode::new(0x60, "RTS", 1, 6, AddressingMode::NoneAddressing),
+        (0x20, "JSR", 3, 6, NoneAddressing),
+        (0x60, "RTS", 1, 6, NoneAddressing),
 
-        OpCode::new(0x40, "RTI", 1, 6, AddressingMode::NoneAddressing),
+        (0x40, "RTI", 1, 6, NoneAddressing),
 
-        OpCode::new(0xd0, "BNE", 2, 2 /*(+1 if branch succeeds +2 if to a new page)*/, AddressingMode::NoneAddressing),
-        OpCode::new(0x70, "BVS", 2, 2 /*(+1 if branch succeeds +2 if to a new page)*/, AddressingMode::NoneAddressing),
-        OpCode::new(0x50, "BVC", 2, 2 /*(+1 if branch succeeds +2 if to a new page)*/, AddressingMode::NoneAddressing),
-        OpCode::new(0x30, "BMI", 2, 2 /*(+1 if branch succeeds +2 if to a new page)*/, AddressingMode::NoneAddressing),
-        OpCode::new(0xf0, "BEQ", 2, 2 /*(+1 if branch succeeds +2 if to a new page)*/, AddressingMode::NoneAddressing),
-        OpCode::new(0xb0, "BCS", 2, 2 /*(+1 if branch succeeds +2 if to a new page)*/, AddressingMode::NoneAddressing),
-        OpCode::new(0x90, "BCC", 2, 2 /*(+1 if branch succeeds +2 if to a new page)*/, AddressingMode::NoneAddressing),
-        OpCode::new(0x10, "BPL", 2, 2 /*(+1 if branch succeeds +2 if to a new page)*/, AddressingMode::NoneAddressing),
+        (0xd0, "BNE", 2, 2 /*(+1 if branch succeeds +2 if to a new page)*/, NoneAddressing),
+        (0x70, "BVS", 2, 2 /*(+1 if branch succeeds +2 if to a new page)*/, NoneAddressing),
+        (0x50, "BVC", 2, 2 /*(+1 if branch succeeds +2 if to a new page)*/, NoneAddressing),
+        (0x30, "BMI", 2, 2 /*(+1 if branch succeeds +2 if to a new page)*/, NoneAddressing),
+        (0xf0, "BEQ", 2, 2 /*(+1 if branch succeeds +2 if to a new page)*/, NoneAddressing),
+        (0xb0, "BCS", 2, 2 /*(+1 if branch succeeds +2 if to a new page)*/, NoneAddressing),
+        (0x90, "BCC", 2, 2 /*(+1 if branch succeeds +2 if to a new page)*/, NoneAddressing),
+        (0x10, "BPL", 2, 2 /*(+1 if branch succeeds +2 if to a new page)*/, NoneAddressing),
 
-        OpCode::new(0x24, "BIT", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x2c, "BIT", 3, 4, AddressingMode::Absolute),
+        (0x24, "BIT", 2, 3, ZeroPage),
+        (0x2c, "BIT", 3, 4, Absolute),
 
 
         /* Stores, Loads */
-        OpCode::new(0xa9, "LDA", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0xa5, "LDA", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0xb5, "LDA", 2, 4, AddressingMode::ZeroPageX),
-        OpCode::new(0xad, "LDA", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0xbd, "LDA", 3, 4/*+1 if page crossed*/, AddressingMode::AbsoluteX),
-        OpCode::new(0xb9, "LDA", 3, 4/*+1 if page crossed*/, AddressingMode::AbsoluteY),
-        OpCode::new(0xa1, "LDA", 2, 6, AddressingMode::IndirectX),
-        OpCode::new(0xb1, "LDA", 2, 5/*+1 if page crossed*/, AddressingMode::IndirectY),
-
-        OpCode::new(0xa2, "LDX", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0xa6, "LDX", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0xb6, "LDX", 2, 4, AddressingMode::ZeroPageY),
-        OpCode::new(0xae, "LDX", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0xbe, "LDX", 3, 4/*+1 if page crossed*/, AddressingMode::AbsoluteY),
-
-        OpCode::new(0xa0, "LDY", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0xa4, "LDY", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0xb4, "LDY", 2, 4, AddressingMode::ZeroPageX),
-        OpCode::new(0xac, "LDY", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0xbc, "LDY", 3, 4/*+1 if page crossed*/, AddressingMode::AbsoluteX),
-
-
-        OpCode::new(0x85, "STA", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x95, "STA", 2, 4, AddressingMode::ZeroPageX),
-        OpCode::new(0x8d, "STA", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0x9d, "STA", 3, 5, AddressingMode::AbsoluteX),
-        OpCode::new(0x99, "STA", 3, 5, AddressingMode::AbsoluteY),
-        OpCode::new(0x81, "STA", 2, 6, AddressingMode::IndirectX),
-        OpCode::new(0x91, "STA", 2, 6, AddressingMode::IndirectY),
-
-        OpCode::new(0x86, "STX", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x96, "STX", 2, 4, AddressingMode::ZeroPageY),
-        OpCode::new(0x8e, "STX", 3, 4, AddressingMode::Absolute),
-
-        OpCode::new(0x84, "STY", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x94, "STY", 2, 4, AddressingMode::ZeroPageX),
-        OpCode::new(0x8c, "STY", 3, 4, AddressingMode::Absolute),
+        (0xa9, "LDA", 2, 2, Immediate),
+        (0xa5, "LDA", 2, 3, ZeroPage),
+        (0xb5, "LDA", 2, 4, ZeroPageX),
+        (0xad, "LDA", 3, 4, Absolute),
+        (0xbd, "LDA", 3, 4/*+1 if page crossed*/, AbsoluteX),
+        (0xb9, "LDA", 3, 4/*+1 if page crossed*/, AbsoluteY),
+        (0xa1, "LDA", 2, 6, IndirectX),
+        (0xb1, "LDA", 2, 5/*+1 if page crossed*/, IndirectY),
+
+        (0xa2, "LDX", 2, 2, Immediate),
+        (0xa6, "LDX", 2, 3, ZeroPage),
+        (0xb6, "LDX", 2, 4, ZeroPageY),
+        (0xae, "LDX", 3, 4, Absolute),
+        (0xbe, "LDX", 3, 4/*+1 if page crossed*/, AbsoluteY),
+
+        (0xa0, "LDY", 2, 2, Immediate),
+        (0xa4, "LDY", 2, 3, ZeroPage),
+        (0xb4, "LDY", 2, 4, ZeroPageX),
+        (0xac, "LDY", 3, 4, Absolute),
+        (0xbc, "LDY", 3, 4/*+1 if page crossed*/, AbsoluteX),
+
+
+        (0x85, "STA", 2, 3, ZeroPage),
+        (0x95, "STA", 2, 4, ZeroPageX),
+        (0x8d, "STA", 3, 4, Absolute),
+        (0x9d, "STA", 3, 5, AbsoluteX),
+        (0x99, "STA", 3, 5, AbsoluteY),
+        (0x81, "STA", 2, 6, IndirectX),
+        (0x91, "STA", 2, 6, IndirectY),
+
+        (0x86, "STX", 2, 3, ZeroPage),
+        (0x96, "STX", 2, 4, ZeroPageY),
+        (0x8e, "STX", 3, 4, Absolute),
+
+        (0x84, "STY", 2, 3, ZeroPage),
+        (0x94, "STY", 2, 4, ZeroPageX),
+        (0x8c, "STY", 3, 4, Absolute),
 
 
         /* Flags clear */
 
-        OpCode::new(0xD8, "CLD", 1, 2, AddressingMode::NoneAddressing),
-        OpCode::new(0x58, "CLI", 1, 2, AddressingMode::NoneAddressing),
-        OpCode::new(0xb8, "CLV", 1, 2, AddressingMode::NoneAddressing),
-        OpCode::new(0x18, "CLC", 1, 2, AddressingMode::NoneAddressing),
-        OpCode::new(0x38, "SEC", 1, 2, AddressingMode::NoneAddressing),
-        OpCode::new(0x78, "SEI", 1, 2, AddressingMode::NoneAddressing),
-        OpCode::new(0xf8, "SED", 1, 2, AddressingMode::NoneAddressing),
-
-        OpCode::new(0xaa, "TAX", 1, 2, AddressingMode::NoneAddressing),
-        OpCode::new(0xa8, "TAY", 1, 2, AddressingMode::NoneAddressing),
-        OpCode::new(0xba, "TSX", 1, 2, AddressingMode::NoneAddressing),
-        OpCode::new(0x8a, "TXA", 1, 2, AddressingMode::NoneAddressing),
-        OpCode::new(0x9a, "TXS", 1, 2, AddressingMode::NoneAddressing),
-        OpCode::new(0x98, "TYA", 1, 2, AddressingMode::NoneAddressing),
+        (0xD8, "CLD", 1, 2, NoneAddressing),
+        (0x58, "CLI", 1, 2, NoneAddressing),
+        (0xb8, "CLV", 1, 2, NoneAddressing),
+        (0x18, "CLC", 1, 2, NoneAddressing),
+        (0x38, "SEC", 1, 2, NoneAddressing),
+        (0x78, "SEI", 1, 2, NoneAddressing),
+        (0xf8, "SED", 1, 2, NoneAddressing),
+
+        (0xaa, "TAX", 1, 2, NoneAddressing),
+        (0xa8, "TAY", 1, 2, NoneAddressing),
+        (0xba, "TSX", 1, 2, NoneAddressing),
+        (0x8a, "TXA", 1, 2, NoneAddressing),
+        (0x9a, "TXS", 1, 2, NoneAddressing),
+        (0x98, "TYA", 1, 2, NoneAddressing),
 
         /* Stack */
-        OpCode::new(0x48, "PHA", 1, 3, AddressingMode::NoneAddressing),
-        OpCode::new(0x68, "PLA", 1, 4, AddressingMode::NoneAddressing),
-        OpCode::new(0x08, "PHP", 1, 3, AddressingMode::NoneAddressing),
-        OpCode::new(0x28, "PLP", 1, 4, AddressingMode::NoneAddressing),
+        (0x48, "PHA", 1, 3, NoneAddressing),
+        (0x68, "PLA", 1, 4, NoneAddressing),
+        (0x08, "PHP", 1, 3, NoneAddressing),
+        (0x28, "PLP", 1, 4, NoneAddressing),
+
+        /* Unofficial */
+        (0x0b, "ANC", 2, 2, Immediate),
+        (0x2b, "ANC", 2, 2, Immediate),
+        (0x4b, "ALR", 2, 2, Immediate),
+        (0x6b, "ARR", 2, 2, Immediate),
+        (0xcb, "AXS", 2, 2, Immediate),
 
     ];
 
@@ -235,4 +267,21 @@ lazy_static::lazy_static! {
         }
         map
     };
+
+    pub static ref OPCODES_TABLE: [Option<&'static OpCode>; 256] = {
+        let mut table = [None; 256];
+        for cpuop in &*CPU_OPS_CODES {
+            table[cpuop.code as usize] = Some(cpuop);
+        }
+        table
+    };
+}
+
+/// Look `code` up in `OPCODES_TABLE` with the `opcode-array` feature, in `OPCODES_MAP` otherwise.
+pub fn decode(code: u8) -> Option<&'static OpCode> {
+    #[cfg(feature = "opcode-array")]
+    return OPCODES_TABLE[code as usize];
+
+    #[cfg(not(feature = "opcode-array"))]
+    return OPCODES_MAP.get(&code).copied();
 }
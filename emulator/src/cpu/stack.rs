@@ -1,9 +1,10 @@
+#[cfg(feature = "tracing")]
 use tracing::instrument;
 
 use super::{memory::Memory, Cpu};
 
-const STACK: u16 = 0x0100;
-const STACK_RESET: u8 = 0xfd;
+pub(crate) const STACK: u16 = 0x0100;
+pub(crate) const STACK_RESET: u8 = 0xfd;
 
 pub trait Stack: Memory {
     fn stack_pop(&mut self) -> u8;
@@ -16,19 +17,22 @@ pub trait Stack: Memory {
 }
 
 impl Stack for Cpu {
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn stack_pop(&mut self) -> u8 {
         self.stack_pointer = self.stack_pointer.wrapping_add(1);
-        self.mem_read((STACK as u16) + self.stack_pointer as u16)
+        self.mem_read(self.stack_page.wrapping_add(self.stack_pointer as u16))
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn stack_push(&mut self, data: u8) {
-        self.mem_write((STACK as u16) + self.stack_pointer as u16, data);
+        self.mem_write(
+            self.stack_page.wrapping_add(self.stack_pointer as u16),
+            data,
+        );
         self.stack_pointer = self.stack_pointer.wrapping_sub(1)
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn stack_push_u16(&mut self, data: u16) {
         let hi = (data >> 8) as u8;
         let lo = (data & 0xff) as u8;
@@ -36,7 +40,7 @@ impl Stack for Cpu {
         self.stack_push(lo);
     }
 
-    #[instrument]
+    #[cfg_attr(feature = "tracing", instrument)]
     fn stack_pop_u16(&mut self) -> u16 {
         let lo = self.stack_pop() as u16;
         let hi = self.stack_pop() as u16;
@@ -44,3 +48,23 @@ impl Stack for Cpu {
         hi << 8 | lo
     }
 }
+
+impl Cpu {
+    /// Push `addr` the way `JSR` does, `RTS` then resumes at `addr + 1`.
+    pub fn push_return_address(&mut self, addr: u16) {
+        self.stack_push_u16(addr);
+    }
+
+    /// Push a raw status byte, as an interrupt would before `RTI`.
+    pub fn push_status(&mut self, status: u8) {
+        self.stack_push(status);
+    }
+
+    /// Bytes currently on the stack, from the most recently pushed one up to the top of the
+    /// stack page.
+    pub fn stack_dump(&self) -> Vec<u8> {
+        let used = 0xFF - self.stack_pointer as usize;
+        let top = self.stack_page.wrapping_add(self.stack_pointer as u16);
+        self.mem_read_range(top.wrapping_add(1), used)
+    }
+}
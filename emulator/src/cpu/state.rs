@@ -0,0 +1,88 @@
+use enumflags2::BitFlags;
+
+use super::{flags::CpuFlags, memory::Memory, Cpu, IRQ_ADDRESS, NMI_ADDRESS, RESET_ADDRESS};
+
+/// Snapshot of the CPU registers, without memory.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CpuState {
+    pub register_a: u8,
+    pub register_x: u8,
+    pub register_y: u8,
+    pub status: BitFlags<CpuFlags>,
+    pub program_counter: u16,
+    pub stack_pointer: u8,
+    pub cycles: usize,
+}
+
+/// Just the programmer visible registers, see [`Cpu::save_registers`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Registers {
+    pub register_a: u8,
+    pub register_x: u8,
+    pub register_y: u8,
+    pub status: BitFlags<CpuFlags>,
+    pub program_counter: u16,
+    pub stack_pointer: u8,
+}
+
+/// Targets of the three vectors at the top of memory, see [`Cpu::vectors`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct InterruptVectors {
+    pub nmi: u16,
+    pub reset: u16,
+    pub irq: u16,
+}
+
+impl CpuState {
+    /// Flags whose value differs in `next`, paired with their new value.
+    pub fn flag_delta(&self, next: &CpuState) -> Vec<(CpuFlags, bool)> {
+        (self.status ^ next.status)
+            .iter()
+            .map(|flag| (flag, next.status.contains(flag)))
+            .collect()
+    }
+}
+
+impl Cpu {
+    pub fn state(&self) -> CpuState {
+        CpuState {
+            register_a: self.register_a,
+            register_x: self.register_x,
+            register_y: self.register_y,
+            status: self.status,
+            program_counter: self.program_counter,
+            stack_pointer: self.stack_pointer,
+            cycles: self.cycles,
+        }
+    }
+
+    pub fn save_registers(&self) -> Registers {
+        Registers {
+            register_a: self.register_a,
+            register_x: self.register_x,
+            register_y: self.register_y,
+            status: self.status,
+            program_counter: self.program_counter,
+            stack_pointer: self.stack_pointer,
+        }
+    }
+
+    /// Put back registers from `save_registers`, memory and the cycle counter are left as is.
+    pub fn restore_registers(&mut self, registers: Registers) {
+        self.register_a = registers.register_a;
+        self.register_x = registers.register_x;
+        self.register_y = registers.register_y;
+        self.status = registers.status;
+        self.program_counter = registers.program_counter;
+        self.stack_pointer = registers.stack_pointer;
+    }
+
+    /// Read the NMI (`0xFFFA`), reset (`0xFFFC`) and IRQ/BRK (`0xFFFE`) vectors.
+    pub fn vectors(&self) -> InterruptVectors {
+        InterruptVectors {
+            nmi: self.peek_u16(NMI_ADDRESS),
+            reset: self.peek_u16(RESET_ADDRESS),
+            irq: self.peek_u16(IRQ_ADDRESS),
+        }
+    }
+}
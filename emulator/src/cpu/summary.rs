@@ -0,0 +1,48 @@
+use std::fmt::Display;
+
+use super::{state::CpuState, Cpu, RunResult};
+
+/// What a run amounted to, see [`Cpu::run_summary`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RunSummary {
+    pub instructions: usize,
+    pub cycles: usize,
+    pub state: CpuState,
+    pub result: RunResult,
+}
+
+impl Display for RunSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} instructions in {} cycles, A=${:02X} X=${:02X} Y=${:02X} P=${:02X} SP=${:02X} PC=${:04X}, ",
+            self.instructions,
+            self.cycles,
+            self.state.register_a,
+            self.state.register_x,
+            self.state.register_y,
+            self.state.status.bits(),
+            self.state.stack_pointer,
+            self.state.program_counter,
+        )?;
+
+        match self.result {
+            RunResult::Running => write!(f, "still running"),
+            RunResult::Done => write!(f, "halted on BRK"),
+            RunResult::Error(error) => write!(f, "halted: {}", error),
+        }
+    }
+}
+
+impl Cpu {
+    /// Instructions and cycles executed since the last reset, the current registers and the
+    /// result of the last step.
+    pub fn run_summary(&self) -> RunSummary {
+        RunSummary {
+            instructions: self.instructions,
+            cycles: self.cycles,
+            state: self.state(),
+            result: self.last_result,
+        }
+    }
+}
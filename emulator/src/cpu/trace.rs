@@ -0,0 +1,150 @@
+use std::io::Write;
+
+use super::{
+    memory::Memory,
+    opcode::{self, OpCode},
+    state::CpuState,
+    Cpu, RunResult,
+};
+
+/// An executed instruction, along with the CPU state right before it ran and the cycles it took.
+#[derive(Debug, Clone)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub code: u8,
+    /// `None` for codes missing from the opcode table, run by a custom handler or skipped in
+    /// lenient mode.
+    pub opcode: Option<&'static OpCode>,
+    pub state: CpuState,
+    pub cycles: usize,
+}
+
+const CONTROL_FLOW: [&str; 12] = [
+    "JMP", "JSR", "RTS", "RTI", "BCC", "BCS", "BEQ", "BMI", "BNE", "BPL", "BVC", "BVS",
+];
+
+/// A jump, call, return or branch, along with where execution continued.
+#[derive(Debug, Clone)]
+pub struct ControlFlowEntry {
+    pub pc: u16,
+    pub opcode: &'static OpCode,
+    pub target: u16,
+}
+
+/// Lazily executes instructions, see [`Cpu::trace_run`].
+pub struct TraceRun<'a> {
+    cpu: &'a mut Cpu,
+    remaining: usize,
+    done: bool,
+}
+
+impl Iterator for TraceRun<'_> {
+    type Item = TraceEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let pc = self.cpu.program_counter;
+        let code = self.cpu.peek(pc);
+        let state = self.cpu.state();
+
+        if self.cpu.run_single_cycle() != RunResult::Running {
+            self.done = true;
+        }
+
+        Some(TraceEntry {
+            pc,
+            code,
+            opcode: opcode::decode(code),
+            state,
+            cycles: self.cpu.last_instruction_cycles(),
+        })
+    }
+}
+
+impl Cpu {
+    /// Execute up to `max` instructions, yielding one entry per instruction as it runs. The
+    /// iterator ends after `BRK`, on an error or once `max` is reached.
+    pub fn trace_run(&mut self, max: usize) -> TraceRun<'_> {
+        TraceRun {
+            cpu: self,
+            remaining: max,
+            done: false,
+        }
+    }
+
+    /// Execute up to `max` instructions like [`Cpu::trace_run`], only recording the control flow
+    /// ones (`JMP`, `JSR`, `RTS`, `RTI` and branches). Branches not taken have the next
+    /// instruction as target.
+    pub fn control_flow_trace(&mut self, max: usize) -> Vec<ControlFlowEntry> {
+        let mut entries = Vec::new();
+
+        for _ in 0..max {
+            let pc = self.program_counter;
+            let opcode = opcode::decode(self.peek(pc));
+            let result = self.run_single_cycle();
+
+            // Codes missing from the table (custom handlers, lenient mode) aren't control flow
+            if let Some(opcode) = opcode.filter(|opcode| CONTROL_FLOW.contains(&opcode.repr)) {
+                entries.push(ControlFlowEntry {
+                    pc,
+                    opcode,
+                    target: self.program_counter,
+                });
+            }
+
+            if result != RunResult::Running {
+                break;
+            }
+        }
+
+        entries
+    }
+
+    /// Write a trace line for every executed instruction to `sink`, see [`Cpu::trace_line`].
+    pub fn set_trace_sink(&mut self, sink: Box<dyn Write>) {
+        self.trace_sink = Some(sink);
+    }
+
+    /// nestest.log style line for the instruction about to run: address, raw bytes, disassembly
+    /// and registers, e.g. right after loading `LDA #$05` and resetting:
+    ///
+    /// ```text
+    /// 0600  A9 05     LDA #$05                        A:00 X:00 Y:00 P:00 SP:00 CYC:7
+    /// ```
+    pub fn trace_line(&self) -> String {
+        let pc = self.program_counter;
+        let bytes = self
+            .mem_read_range(pc, self.instruction_len_at(pc) as usize)
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        format!(
+            "{:04X}  {:<8}  {:<30}  A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
+            pc,
+            bytes,
+            self.disassemble(pc),
+            self.register_a,
+            self.register_x,
+            self.register_y,
+            self.status.bits(),
+            self.stack_pointer,
+            self.cycles,
+        )
+    }
+
+    pub(crate) fn write_trace(&mut self) {
+        let line = self.trace_line();
+        if let Some(sink) = self.trace_sink.as_mut() {
+            if let Err(error) = writeln!(sink, "{}", line) {
+                log::warn!("Dropping trace sink after write error: {}", error);
+                self.trace_sink = None;
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// Cycles to emulate per animation frame. Halves after a slow frame and grows back by `min`
+/// cycles after a fast one, staying within `min..=max`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FrameBudget {
+    cycles: usize,
+    min: usize,
+    max: usize,
+    slow_frame_ms: f64,
+}
+
+impl FrameBudget {
+    /// Starts at `max` cycles. A frame counts as slow once it takes `slow_frame_ms` or more.
+    pub fn new(min: usize, max: usize, slow_frame_ms: f64) -> Self {
+        Self {
+            cycles: max,
+            min,
+            max,
+            slow_frame_ms,
+        }
+    }
+
+    pub fn cycles(&self) -> usize {
+        self.cycles
+    }
+
+    /// Adjust the budget to how long the last frame took to emulate.
+    pub fn adapt(&mut self, elapsed_ms: f64) {
+        if elapsed_ms >= self.slow_frame_ms {
+            self.cycles = (self.cycles / 2).max(self.min);
+        } else if elapsed_ms < self.slow_frame_ms / 2.0 {
+            self.cycles = (self.cycles + self.min).min(self.max);
+        }
+    }
+}
+
+/// Nearest-neighbour upscale of an rgba frame, so the canvas stays crisp at any scale instead of
+/// being stretched (and blurred) by css.
+pub fn upscale(screen_state: &[u8], scale: usize) -> Vec<u8> {
+    let width = SCREEN_WIDTH * scale;
+    let mut scaled = vec![0; width * SCREEN_HEIGHT * scale * 4];
+
+    for y in 0..SCREEN_HEIGHT * scale {
+        for x in 0..width {
+            let source = ((y / scale) * SCREEN_WIDTH + x / scale) * 4;
+            let target = (y * width + x) * 4;
+            scaled[target..target + 4].copy_from_slice(&screen_state[source..source + 4]);
+        }
+    }
+
+    scaled
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frame_budget_adapts_to_frame_time() {
+        let mut budget = FrameBudget::new(500, 4_000, 16.0);
+        assert_eq!(budget.cycles(), 4_000);
+
+        budget.adapt(16.0);
+        assert_eq!(budget.cycles(), 2_000);
+        budget.adapt(12.0);
+        assert_eq!(budget.cycles(), 2_000);
+        for _ in 0..4 {
+            budget.adapt(30.0);
+        }
+        assert_eq!(budget.cycles(), 500);
+
+        budget.adapt(2.0);
+        assert_eq!(budget.cycles(), 1_000);
+        for _ in 0..10 {
+            budget.adapt(2.0);
+        }
+        assert_eq!(budget.cycles(), 4_000);
+    }
+
+    #[test]
+    fn test_upscale() {
+        let mut screen = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 4];
+        screen[..4].copy_from_slice(&[1, 2, 3, 255]);
+
+        let scaled = upscale(&screen, 2);
+        let width = SCREEN_WIDTH * 2;
+        assert_eq!(scaled.len(), width * SCREEN_HEIGHT * 2 * 4);
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let pixel = (y * width + x) * 4;
+            assert_eq!(scaled[pixel..pixel + 4], [1, 2, 3, 255]);
+        }
+        assert_eq!(scaled[8..12], [0, 0, 0, 0]);
+        assert_eq!(upscale(&screen, 1), screen);
+    }
+}
@@ -0,0 +1,153 @@
+use std::{cell::RefCell, rc::Rc};
+
+use enumflags2::{bitflags, BitFlags};
+
+use crate::memory::IoHandler;
+
+/// Controller port of the first joypad.
+pub const JOYPAD_1_ADDRESS: u16 = 0x4016;
+
+/// Buttons of a standard NES controller, in the order the shift register reports them.
+#[repr(u8)]
+#[bitflags]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum JoypadButton {
+    A = 1 << 0,
+    B = 1 << 1,
+    Select = 1 << 2,
+    Start = 1 << 3,
+    Up = 1 << 4,
+    Down = 1 << 5,
+    Left = 1 << 6,
+    Right = 1 << 7,
+}
+
+/// Map a keyboard key, as given by `KeyboardEvent.key`, to a button. Both WASD and the arrow
+/// keys move, `x` and `z` are A and B. Matching is case insensitive.
+pub fn button_from_key(key: &str) -> Option<JoypadButton> {
+    let button = match key.to_lowercase().as_str() {
+        "w" | "arrowup" => JoypadButton::Up,
+        "s" | "arrowdown" => JoypadButton::Down,
+        "a" | "arrowleft" => JoypadButton::Left,
+        "d" | "arrowright" => JoypadButton::Right,
+        "x" => JoypadButton::A,
+        "z" => JoypadButton::B,
+        "enter" => JoypadButton::Start,
+        "shift" => JoypadButton::Select,
+        _ => return None,
+    };
+
+    Some(button)
+}
+
+impl JoypadButton {
+    /// Byte the snake demo expects at `LAST_PRESSED_BUTTON_ADDRESS`, the ascii code of its WASD
+    /// key. Only directions are used.
+    pub fn snake_keycode(self) -> Option<u8> {
+        match self {
+            JoypadButton::Up => Some(b'w'),
+            JoypadButton::Down => Some(b's'),
+            JoypadButton::Left => Some(b'a'),
+            JoypadButton::Right => Some(b'd'),
+            _ => None,
+        }
+    }
+}
+
+/// Standard controller, meant to be mapped at [`JOYPAD_1_ADDRESS`] with `Cpu::map_io`. Writing an
+/// odd value holds the strobe, which keeps reloading the shift register; once released, each read
+/// returns the next button (A first) in bit 0, and `1` after all eight.
+#[derive(Debug, Default)]
+pub struct Joypad {
+    buttons: BitFlags<JoypadButton>,
+    strobe: bool,
+    index: u8,
+}
+
+impl Joypad {
+    pub fn set_pressed(&mut self, button: JoypadButton, pressed: bool) {
+        self.buttons.set(button, pressed);
+    }
+
+    pub fn buttons(&self) -> BitFlags<JoypadButton> {
+        self.buttons
+    }
+}
+
+impl IoHandler for Joypad {
+    fn read(&mut self, _addr: u16) -> u8 {
+        if self.index > 7 {
+            return 1;
+        }
+
+        let bit = (self.buttons.bits() >> self.index) & 1;
+        if !self.strobe {
+            self.index += 1;
+        }
+
+        bit
+    }
+
+    fn write(&mut self, _addr: u16, data: u8) {
+        self.strobe = data & 1 == 1;
+        if self.strobe {
+            self.index = 0;
+        }
+    }
+}
+
+/// Lets the frontend keep a handle on a mapped joypad to press buttons.
+impl IoHandler for Rc<RefCell<Joypad>> {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.borrow_mut().read(addr)
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        self.borrow_mut().write(addr, data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{memory::Memory, Cpu};
+
+    #[test]
+    fn test_button_from_key() {
+        assert_eq!(button_from_key("w"), Some(JoypadButton::Up));
+        assert_eq!(button_from_key("ArrowUp"), Some(JoypadButton::Up));
+        assert_eq!(button_from_key("q"), None);
+        assert_eq!(JoypadButton::Up.snake_keycode(), Some(0x77));
+        assert_eq!(JoypadButton::Start.snake_keycode(), None);
+    }
+
+    #[test]
+    fn test_joypad_strobe_and_reads() {
+        let joypad = Rc::new(RefCell::new(Joypad::default()));
+        joypad.borrow_mut().set_pressed(JoypadButton::A, true);
+        joypad.borrow_mut().set_pressed(JoypadButton::Up, true);
+
+        let mut cpu = Cpu::default();
+        cpu.map_io(
+            JOYPAD_1_ADDRESS..=JOYPAD_1_ADDRESS,
+            Box::new(joypad.clone()),
+        );
+
+        // Reads keep returning A while the strobe is held
+        cpu.mem_write(JOYPAD_1_ADDRESS, 1);
+        assert_eq!(cpu.mem_read(JOYPAD_1_ADDRESS), 1);
+        assert_eq!(cpu.mem_read(JOYPAD_1_ADDRESS), 1);
+        cpu.mem_write(JOYPAD_1_ADDRESS, 0);
+
+        let reads = (0..9)
+            .map(|_| cpu.mem_read(JOYPAD_1_ADDRESS))
+            .collect::<Vec<_>>();
+        assert_eq!(reads, vec![1, 0, 0, 0, 1, 0, 0, 0, 1]);
+
+        // Strobing again reloads the register
+        joypad.borrow_mut().set_pressed(JoypadButton::A, false);
+        cpu.mem_write(JOYPAD_1_ADDRESS, 1);
+        cpu.mem_write(JOYPAD_1_ADDRESS, 0);
+        assert_eq!(cpu.mem_read(JOYPAD_1_ADDRESS), 0);
+    }
+}
@@ -1,8 +1,12 @@
 pub mod cpu;
+pub mod frame;
+pub mod joypad;
 
 pub use cpu::*;
 
 pub const RNG_ADDRESS: u8 = 0xFE;
+pub const SCREEN_WIDTH: usize = 32;
+pub const SCREEN_HEIGHT: usize = 32;
 pub const LAST_PRESSED_BUTTON_ADDRESS: u8 = 0xFF;
 
 // wip to be removed, used for testing purposes
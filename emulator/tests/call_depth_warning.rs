@@ -0,0 +1,24 @@
+mod common;
+
+use emulator::Cpu;
+use log::{Level, LevelFilter};
+
+#[test]
+fn test_deep_jsr_nesting_warns_past_threshold() {
+    common::install_logger(LevelFilter::Warn);
+
+    // Five nested calls, each subroutine calling the next one
+    let mut program = Vec::new();
+    for level in 1..=5u16 {
+        let target = 0x0600 + level * 3;
+        program.extend([0x20, target as u8, (target >> 8) as u8]);
+    }
+    program.push(0x00);
+
+    let mut cpu = Cpu::default();
+    cpu.set_call_depth_warning(Some(3));
+    cpu.load_and_run(program);
+
+    assert_eq!(cpu.call_depth(), 5);
+    assert_eq!(common::captured(Level::Warn, "JSR nesting").len(), 2);
+}
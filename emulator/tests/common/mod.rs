@@ -0,0 +1,43 @@
+//! Logger capturing every record, for the integration tests asserting on log output. Each test
+//! binary gets its own global logger, so install it once per file.
+
+use std::sync::Mutex;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+static RECORDS: Mutex<Vec<(Level, String)>> = Mutex::new(Vec::new());
+
+struct CapturingLogger;
+
+impl Log for CapturingLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        RECORDS
+            .lock()
+            .unwrap()
+            .push((record.level(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger;
+
+pub fn install_logger(max_level: LevelFilter) {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(max_level);
+}
+
+/// Captured messages logged at `level` and starting with `prefix`.
+pub fn captured(level: Level, prefix: &str) -> Vec<String> {
+    RECORDS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(record_level, message)| *record_level == level && message.starts_with(prefix))
+        .map(|(_, message)| message.clone())
+        .collect()
+}
@@ -0,0 +1,182 @@
+//! Runs random programs through both the emulator and a tiny reference implementation of a
+//! subset of the instruction set, comparing registers, flags and memory after each instruction.
+
+use emulator::{flags::CpuFlags, memory::Memory, Cpu};
+
+const PROGRAMS: usize = 500;
+const INSTRUCTIONS_PER_PROGRAM: usize = 32;
+const CELL: u8 = 0x10;
+
+// (opcode, has an operand)
+const INSTRUCTIONS: [(u8, bool); 27] = [
+    (0xA9, true),  // LDA #
+    (0xA2, true),  // LDX #
+    (0xA0, true),  // LDY #
+    (0xAA, false), // TAX
+    (0xA8, false), // TAY
+    (0x8A, false), // TXA
+    (0x98, false), // TYA
+    (0xE8, false), // INX
+    (0xC8, false), // INY
+    (0xCA, false), // DEX
+    (0x88, false), // DEY
+    (0x18, false), // CLC
+    (0x38, false), // SEC
+    (0x69, true),  // ADC #
+    (0xE9, true),  // SBC #
+    (0x29, true),  // AND #
+    (0x09, true),  // ORA #
+    (0x49, true),  // EOR #
+    (0xC9, true),  // CMP #
+    (0xE0, true),  // CPX #
+    (0xC0, true),  // CPY #
+    (0x85, true),  // STA $10
+    (0xA5, true),  // LDA $10
+    (0xE6, true),  // INC $10
+    (0xC6, true),  // DEC $10
+    (0x4A, false), // LSR A
+    (0x0A, false), // ASL A
+];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct State {
+    a: u8,
+    x: u8,
+    y: u8,
+    carry: bool,
+    zero: bool,
+    negative: bool,
+    overflow: bool,
+    cell: u8,
+}
+
+impl State {
+    fn from_cpu(cpu: &Cpu) -> Self {
+        State {
+            a: cpu.register_a,
+            x: cpu.register_x,
+            y: cpu.register_y,
+            carry: cpu.status.contains(CpuFlags::CarryBit),
+            zero: cpu.status.contains(CpuFlags::Zero),
+            negative: cpu.status.contains(CpuFlags::Negative),
+            overflow: cpu.status.contains(CpuFlags::Overflow),
+            cell: cpu.mem_read(CELL as u16),
+        }
+    }
+
+    fn set_zero_and_negative(&mut self, value: u8) -> u8 {
+        self.zero = value == 0;
+        self.negative = value & 0x80 != 0;
+
+        value
+    }
+
+    fn adc(&mut self, value: u8) {
+        let sum = self.a as u16 + value as u16 + self.carry as u16;
+        let result = sum as u8;
+
+        self.carry = sum > 0xFF;
+        self.overflow = (self.a ^ result) & (value ^ result) & 0x80 != 0;
+        self.a = self.set_zero_and_negative(result);
+    }
+
+    fn compare(&mut self, register: u8, value: u8) {
+        self.carry = register >= value;
+        self.set_zero_and_negative(register.wrapping_sub(value));
+    }
+
+    fn step(&mut self, code: u8, operand: u8) {
+        match code {
+            0xA9 => self.a = self.set_zero_and_negative(operand),
+            0xA2 => self.x = self.set_zero_and_negative(operand),
+            0xA0 => self.y = self.set_zero_and_negative(operand),
+            0xAA => self.x = self.set_zero_and_negative(self.a),
+            0xA8 => self.y = self.set_zero_and_negative(self.a),
+            0x8A => self.a = self.set_zero_and_negative(self.x),
+            0x98 => self.a = self.set_zero_and_negative(self.y),
+            0xE8 => self.x = self.set_zero_and_negative(self.x.wrapping_add(1)),
+            0xC8 => self.y = self.set_zero_and_negative(self.y.wrapping_add(1)),
+            0xCA => self.x = self.set_zero_and_negative(self.x.wrapping_sub(1)),
+            0x88 => self.y = self.set_zero_and_negative(self.y.wrapping_sub(1)),
+            0x18 => self.carry = false,
+            0x38 => self.carry = true,
+            0x69 => self.adc(operand),
+            0xE9 => self.adc(!operand),
+            0x29 => self.a = self.set_zero_and_negative(self.a & operand),
+            0x09 => self.a = self.set_zero_and_negative(self.a | operand),
+            0x49 => self.a = self.set_zero_and_negative(self.a ^ operand),
+            0xC9 => self.compare(self.a, operand),
+            0xE0 => self.compare(self.x, operand),
+            0xC0 => self.compare(self.y, operand),
+            0x85 => self.cell = self.a,
+            0xA5 => self.a = self.set_zero_and_negative(self.cell),
+            0xE6 => self.cell = self.set_zero_and_negative(self.cell.wrapping_add(1)),
+            0xC6 => self.cell = self.set_zero_and_negative(self.cell.wrapping_sub(1)),
+            0x4A => {
+                self.carry = self.a & 1 == 1;
+                self.a = self.set_zero_and_negative(self.a >> 1);
+            }
+            0x0A => {
+                self.carry = self.a >> 7 == 1;
+                self.a = self.set_zero_and_negative(self.a << 1);
+            }
+            _ => unreachable!("Opcode 0x{:02X} is not part of the reference subset", code),
+        }
+    }
+}
+
+/// xorshift32, good enough to generate programs without pulling in a dependency.
+struct Rng(u32);
+
+impl Rng {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+#[test]
+fn test_emulator_matches_reference_implementation() {
+    let mut rng = Rng(0x6502_6502);
+
+    for program_index in 0..PROGRAMS {
+        let mut instructions = Vec::with_capacity(INSTRUCTIONS_PER_PROGRAM);
+        let mut program = Vec::new();
+        for _ in 0..INSTRUCTIONS_PER_PROGRAM {
+            let (code, has_operand) = INSTRUCTIONS[rng.next() as usize % INSTRUCTIONS.len()];
+            let operand = match code {
+                0x85 | 0xA5 | 0xE6 | 0xC6 => CELL,
+                _ if has_operand => rng.next() as u8,
+                _ => 0,
+            };
+
+            program.push(code);
+            if has_operand {
+                program.push(operand);
+            }
+            instructions.push((code, operand));
+        }
+        program.push(0x00);
+
+        let mut cpu = Cpu::default();
+        cpu.load(program);
+        cpu.reset();
+        let mut reference = State::from_cpu(&cpu);
+
+        for (code, operand) in instructions {
+            cpu.run_single_cycle();
+            reference.step(code, operand);
+
+            assert_eq!(
+                State::from_cpu(&cpu),
+                reference,
+                "Emulator diverged from the reference after opcode 0x{:02X} (operand 0x{:02X}) in program #{}",
+                code,
+                operand,
+                program_index
+            );
+        }
+    }
+}
@@ -0,0 +1,20 @@
+mod common;
+
+use emulator::{Cpu, SNAKE};
+use log::{Level, LevelFilter};
+
+#[test]
+fn test_dump_on_load_logs_the_program() {
+    common::install_logger(LevelFilter::Debug);
+
+    let mut cpu = Cpu::default();
+    cpu.load(SNAKE.to_vec());
+    assert!(common::captured(Level::Debug, "Loaded program").is_empty());
+
+    cpu.set_dump_on_load(true);
+    cpu.load(SNAKE.to_vec());
+
+    let messages = common::captured(Level::Debug, "Loaded program");
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].contains("JSR $0606"), "{}", messages[0]);
+}
@@ -0,0 +1,24 @@
+mod common;
+
+use emulator::{memory::Memory, Cpu};
+use log::{Level, LevelFilter};
+
+fn memory_events() -> usize {
+    common::captured(Level::Trace, "Reading memory address").len()
+        + common::captured(Level::Trace, "Writing 0x").len()
+}
+
+#[test]
+fn test_memory_tracing_is_opt_in() {
+    common::install_logger(LevelFilter::Trace);
+
+    let mut cpu = Cpu::default();
+    cpu.mem_write(0x10, 0x55);
+    cpu.mem_read(0x10);
+    assert_eq!(memory_events(), 0);
+
+    cpu.set_trace_memory(true);
+    cpu.mem_write(0x10, 0x55);
+    cpu.mem_read(0x10);
+    assert_eq!(memory_events(), 2);
+}
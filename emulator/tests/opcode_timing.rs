@@ -0,0 +1,42 @@
+//! Checks the base cycle counts of the opcode table against the canonical NMOS 6502 timings,
+//! see https://www.nesdev.org/wiki/6502_cycle_times. Page crossing and taken branch penalties
+//! aren't part of the base count.
+
+use emulator::opcode::CPU_OPS_CODES;
+
+#[rustfmt::skip]
+const CYCLES: [u8; 256] = [
+//  x0 x1 x2 x3 x4 x5 x6 x7 x8 x9 xA xB xC xD xE xF
+    7, 6, 0, 8, 3, 3, 5, 5, 3, 2, 2, 2, 4, 4, 6, 6, // 0x
+    2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 1x
+    6, 6, 0, 8, 3, 3, 5, 5, 4, 2, 2, 2, 4, 4, 6, 6, // 2x
+    2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 3x
+    6, 6, 0, 8, 3, 3, 5, 5, 3, 2, 2, 2, 3, 4, 6, 6, // 4x
+    2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 5x
+    6, 6, 0, 8, 3, 3, 5, 5, 4, 2, 2, 2, 5, 4, 6, 6, // 6x
+    2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 7x
+    2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4, // 8x
+    2, 6, 0, 6, 4, 4, 4, 4, 2, 5, 2, 5, 5, 5, 5, 5, // 9x
+    2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4, // Ax
+    2, 5, 0, 5, 4, 4, 4, 4, 2, 4, 2, 4, 4, 4, 4, 4, // Bx
+    2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, // Cx
+    2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // Dx
+    2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, // Ex
+    2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // Fx
+];
+
+#[test]
+fn test_opcode_cycles_match_reference() {
+    let mismatches = CPU_OPS_CODES
+        .iter()
+        .filter(|opcode| opcode.cycles != CYCLES[opcode.code as usize])
+        .map(|opcode| {
+            format!(
+                "0x{:02X} {}: {} cycles, expected {}",
+                opcode.code, opcode.repr, opcode.cycles, CYCLES[opcode.code as usize]
+            )
+        })
+        .collect::<Vec<_>>();
+
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
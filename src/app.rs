@@ -1,15 +1,28 @@
-use emulator::{memory::Memory, Cpu, RunResult, LAST_PRESSED_BUTTON_ADDRESS};
+use emulator::{
+    frame::{upscale, FrameBudget},
+    joypad::{button_from_key, JoypadButton},
+    memory::Memory,
+    Cpu, RunResult, SCREEN_HEIGHT, SCREEN_WIDTH,
+};
 use leptos::{
-    component, create_effect, create_node_ref, create_signal, ev::KeyboardEvent, html,
-    leptos_dom::logging::console_warn, view, IntoView, Signal, SignalGet, SignalSet, SignalUpdate,
-    SignalWith,
+    component, create_effect, create_node_ref, create_signal, ev::KeyboardEvent,
+    event_target_value, html, request_animation_frame, view, IntoView, Signal, SignalGet,
+    SignalGetUntracked, SignalSet, SignalUpdate, SignalWith, SignalWithUntracked, StoredValue,
 };
 use leptos_use::use_raf_fn;
-use rand::Rng;
 use wasm_bindgen::{prelude::*, Clamped};
-use web_sys::{CanvasRenderingContext2d, ImageData};
+use web_sys::{CanvasRenderingContext2d, HtmlAnchorElement, HtmlCanvasElement, ImageData};
 
-const CANVAS_MESSAGE: &'static str = "Could not acquire canvas 2d context";
+const CANVAS_MESSAGE: &str = "Could not acquire canvas 2d context";
+const DEFAULT_SCALE: usize = 10;
+const MAX_SCALE: usize = 20;
+// Cycles emulated per animation frame, run in chunks so a heavy frame can give the browser back
+// control once its deadline is over and pick up on the next frame.
+const CYCLES_PER_FRAME: usize = 4_000;
+const CYCLES_PER_CHUNK: usize = 500;
+// Frames are cut off after this long, and the per-frame budget shrinks whenever that happens so
+// the next frames fit. It grows back to `CYCLES_PER_FRAME` on fast frames.
+const SLOW_FRAME_MS: f64 = 16.0;
 
 #[derive(Default, Copy, Clone, PartialEq)]
 enum GameState {
@@ -24,12 +37,20 @@ pub fn App() -> impl IntoView {
     let (game_state, set_game_state) = create_signal(GameState::default());
     let (cpu, set_cpu) = create_signal({
         let mut cpu = emulator::Cpu::default();
+        cpu.set_rng_seed(rand::random());
         cpu.load(emulator::SNAKE.to_vec());
         cpu.reset();
         cpu
     });
     let running = move || matches!(game_state.get(), GameState::Running);
     let paused = move || matches!(game_state.get(), GameState::Paused);
+    let (scale, set_scale) = create_signal(DEFAULT_SCALE);
+    let (error, set_error) = create_signal(None::<String>);
+    let cycle_budget = StoredValue::new(FrameBudget::new(
+        CYCLES_PER_CHUNK,
+        CYCLES_PER_FRAME,
+        SLOW_FRAME_MS,
+    ));
 
     // Canvas
     let canvas_ref = create_node_ref::<html::Canvas>();
@@ -43,34 +64,57 @@ pub fn App() -> impl IntoView {
 
         let ctx = canvas
             .get_context("2d")
-            .expect(&CANVAS_MESSAGE)
-            .expect(&CANVAS_MESSAGE)
+            .expect(CANVAS_MESSAGE)
+            .expect(CANVAS_MESSAGE)
             .dyn_into::<CanvasRenderingContext2d>()
-            .expect(&CANVAS_MESSAGE);
+            .expect(CANVAS_MESSAGE);
 
         Some(ctx)
     });
 
-    let run_next_cycle = move || {
-        set_cpu.update(|cpu| cpu.mem_write(0xfe, rand::thread_rng().gen_range(1..16)));
-
-        cpu.with(|cpu| {
-            let screen_state = read_screen_state(cpu);
-            // console_warn(&format!("{:?}", &screen_state));
+    let draw_screen = move || {
+        cpu.with_untracked(|cpu| {
+            let Some(canvas_ctx) = canvas_ctx.get_untracked() else {
+                return;
+            };
+            let scale = scale.get_untracked();
+            let screen_state = upscale(&read_screen_state(cpu), scale);
             let screen_state = Clamped(&screen_state[..]);
 
-            let image_data =
-                ImageData::new_with_u8_clamped_array_and_sh(screen_state, 32, 32).unwrap();
+            let image_data = ImageData::new_with_u8_clamped_array_and_sh(
+                screen_state,
+                (SCREEN_WIDTH * scale) as u32,
+                (SCREEN_HEIGHT * scale) as u32,
+            )
+            .unwrap();
 
-            let canvas_ctx = canvas_ctx.get().unwrap();
-            canvas_ctx.scale(10.0, 10.0).unwrap();
             canvas_ctx.put_image_data(&image_data, 0.0, 0.0).unwrap();
         });
+    };
+
+    let run_next_frame = move || {
+        set_cpu.update(|cpu| {
+            let budget = cycle_budget.get_value().cycles();
+            let start = js_sys::Date::now();
+            let deadline = start + SLOW_FRAME_MS;
+            let mut cycles = 0;
+            while cycles < budget && !cpu.is_halted() && js_sys::Date::now() < deadline {
+                // Snake reads a fresh random byte before every instruction
+                cycles += cpu.run_cycles_with_rng(CYCLES_PER_CHUNK.min(budget - cycles));
+            }
+            cycle_budget.update_value(|budget| budget.adapt(js_sys::Date::now() - start));
 
-        set_cpu.update(|cpu| match cpu.run_single_cycle() {
-            RunResult::Running => {}
-            RunResult::Done => set_game_state.set(GameState::Paused),
+            match cpu.run_summary().result {
+                RunResult::Running => {}
+                RunResult::Done => set_game_state.set(GameState::Paused),
+                RunResult::Error(error) => {
+                    set_error.set(Some(error.to_string()));
+                    set_game_state.set(GameState::Paused);
+                }
+            }
         });
+
+        draw_screen();
     };
 
     // create_effect(move |_| {
@@ -92,7 +136,13 @@ pub fn App() -> impl IntoView {
     //     }
     // });
 
-    let game_loop = use_raf_fn(move |_| run_next_cycle());
+    // Resizing the canvas clears it, repaint once the new size is applied, even while paused
+    create_effect(move |_| {
+        scale.track();
+        request_animation_frame(draw_screen);
+    });
+
+    let game_loop = use_raf_fn(move |_| run_next_frame());
     (game_loop.pause)();
 
     create_effect(move |_| {
@@ -103,29 +153,53 @@ pub fn App() -> impl IntoView {
     });
 
     let on_keypress = move |e: KeyboardEvent| {
-        let keycode: u8 = match e.key().to_lowercase().as_str() {
-            "w" => 0x77,
-            "s" => 0x73,
-            "a" => 0x61,
-            "d" => 0x64,
-            _ => return,
+        let Some(keycode) = button_from_key(&e.key()).and_then(JoypadButton::snake_keycode) else {
+            return;
         };
         e.prevent_default();
 
-        set_cpu.update(|cpu| cpu.mem_write(LAST_PRESSED_BUTTON_ADDRESS.into(), keycode));
+        set_cpu.update(|cpu| cpu.set_input(keycode));
         log::debug!(
             "Last pressed button: 0x{:X?}",
-            cpu.with(|cpu| cpu.mem_read(LAST_PRESSED_BUTTON_ADDRESS.into()))
+            cpu.with(|cpu| cpu.peek(cpu.input_address()))
         );
     };
 
     view! {
         <main id="container">
-            <canvas autofocus _ref={canvas_ref} id="screen" on:keypress={on_keypress} tabindex="0" />
+            <canvas
+                autofocus
+                _ref={canvas_ref}
+                id="screen"
+                width={move || SCREEN_WIDTH * scale.get()}
+                height={move || SCREEN_HEIGHT * scale.get()}
+                on:keypress={on_keypress}
+                tabindex="0"
+            />
+            {move || error.get().map(|error| view! { <p id="error">{error}</p> })}
             <section id="controls">
                 <button disabled={running} on:click={move |_| set_game_state.set(GameState::Running)}>Start</button>
                 <button disabled={paused} on:click={move |_| set_game_state.set(GameState::Paused)}>Stop</button>
-                <button disabled={running} on:click={move|_| run_next_cycle()}>{"Advance 1 frame"}</button>
+                <button disabled={running} on:click={move|_| run_next_frame()}>{"Advance 1 frame"}</button>
+                <button on:click={move |_| {
+                    if let Err(error) = cpu.with(save_screenshot) {
+                        log::error!("Could not save screenshot: {:?}", error);
+                    }
+                }}>Screenshot</button>
+                <label>
+                    "Scale "
+                    <input
+                        type="range"
+                        min="1"
+                        max={MAX_SCALE}
+                        prop:value={move || scale.get()}
+                        on:input={move |e| {
+                            if let Ok(value) = event_target_value(&e).parse::<usize>() {
+                                set_scale.set(value.clamp(1, MAX_SCALE));
+                            }
+                        }}
+                    />
+                </label>
             </section>
         </main>
     }
@@ -133,15 +207,14 @@ pub fn App() -> impl IntoView {
 
 // Screen is 32x32, and has four color channels (rgba) (A will always be 255, but it is required
 // within the canvas api)
-fn read_screen_state(cpu: &Cpu) -> [u8; 32 * 32 * 4] {
-    let mut screen_state = [0; 32 * 32 * 4];
+fn read_screen_state(cpu: &Cpu) -> [u8; SCREEN_WIDTH * SCREEN_HEIGHT * 4] {
+    let mut screen_state = [0; SCREEN_WIDTH * SCREEN_HEIGHT * 4];
 
     // Games will place pixels between these two addresses in memory
     (0x0200..0x0600)
-        .into_iter()
         .enumerate()
         .for_each(|(frame_index, memory_address)| {
-            let color_idx = cpu.mem_read(memory_address as u16);
+            let color_idx = cpu.peek(memory_address as u16);
             let (r, g, b) = color(color_idx);
 
             let screen_index = frame_index * 4;
@@ -154,6 +227,41 @@ fn read_screen_state(cpu: &Cpu) -> [u8; 32 * 32 * 4] {
     screen_state
 }
 
+/// Download the current frame as a png, at the native 32x32 resolution rather than the canvas's
+/// scaled one.
+fn save_screenshot(cpu: &Cpu) -> Result<(), JsValue> {
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("No document")?;
+
+    let canvas = document
+        .create_element("canvas")?
+        .dyn_into::<HtmlCanvasElement>()?;
+    canvas.set_width(SCREEN_WIDTH as u32);
+    canvas.set_height(SCREEN_HEIGHT as u32);
+
+    let ctx = canvas
+        .get_context("2d")?
+        .ok_or(CANVAS_MESSAGE)?
+        .dyn_into::<CanvasRenderingContext2d>()?;
+    let screen_state = read_screen_state(cpu);
+    let image_data = ImageData::new_with_u8_clamped_array_and_sh(
+        Clamped(&screen_state[..]),
+        SCREEN_WIDTH as u32,
+        SCREEN_HEIGHT as u32,
+    )?;
+    ctx.put_image_data(&image_data, 0.0, 0.0)?;
+
+    let link = document
+        .create_element("a")?
+        .dyn_into::<HtmlAnchorElement>()?;
+    link.set_href(&canvas.to_data_url_with_type("image/png")?);
+    link.set_download("screenshot.png");
+    link.click();
+
+    Ok(())
+}
+
 /// Map a NES color id to an rgb sequence
 fn color(byte: u8) -> (u8, u8, u8) {
     match byte {